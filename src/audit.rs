@@ -0,0 +1,157 @@
+use crate::error::*;
+use crate::message::ChatMode;
+use crate::moderation::BanTarget;
+use crate::time::unix_secs;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
+use uuid::Uuid;
+
+/// A moderation action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AuditAction {
+    Ban { user: Uuid },
+    ShadowBan { user: Uuid },
+    Unban { user: Uuid },
+    BanTarget { target: BanTarget },
+    UnbanTarget { target: BanTarget },
+    ResolveReport { id: u64 },
+    SetChatMode { mode: ChatMode },
+    RevokeTokens { user: Uuid },
+}
+
+impl AuditAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditAction::Ban { .. } => "Ban",
+            AuditAction::ShadowBan { .. } => "ShadowBan",
+            AuditAction::Unban { .. } => "Unban",
+            AuditAction::BanTarget { .. } => "BanTarget",
+            AuditAction::UnbanTarget { .. } => "UnbanTarget",
+            AuditAction::ResolveReport { .. } => "ResolveReport",
+            AuditAction::SetChatMode { .. } => "SetChatMode",
+            AuditAction::RevokeTokens { .. } => "RevokeTokens",
+        }
+    }
+
+    /// Returns the user the action was applied to.
+    pub fn user(&self) -> Option<&Uuid> {
+        match self {
+            AuditAction::Ban { user }
+            | AuditAction::ShadowBan { user }
+            | AuditAction::Unban { user }
+            | AuditAction::RevokeTokens { user } => Some(user),
+            AuditAction::BanTarget { .. }
+            | AuditAction::UnbanTarget { .. }
+            | AuditAction::ResolveReport { .. }
+            | AuditAction::SetChatMode { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// The time of the action in seconds since the unix epoch.
+    pub time: u64,
+    /// The moderator who performed the action.
+    pub actor: Uuid,
+    pub action: AuditAction,
+    pub reason: Option<String>,
+}
+
+/// Restricts which entries of the audit log are returned.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct AuditFilter {
+    pub actor: Option<Uuid>,
+    pub user: Option<Uuid>,
+    /// The name of the action, e.g. `Ban`.
+    pub action: Option<String>,
+    /// Only entries at or after this time in seconds since the unix epoch are returned.
+    pub since: Option<u64>,
+    /// The maximum amount of entries.
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor.is_none_or(|actor| actor == entry.actor)
+            && self
+                .user
+                .is_none_or(|user| entry.action.user() == Some(&user))
+            && self
+                .action
+                .as_ref()
+                .is_none_or(|action| action == entry.action.name())
+            && self.since.is_none_or(|since| entry.time >= since)
+    }
+}
+
+/// An append-only log of every moderation action, stored as JSON lines.
+pub struct AuditLog {
+    path: PathBuf,
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// The maximum amount of entries returned by `query`.
+    const MAX_QUERY: usize = 1000;
+
+    pub fn new(path: PathBuf) -> Result<AuditLog> {
+        let mut entries = Vec::new();
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if !line.is_empty() {
+                        entries.push(serde_json::from_str(&line)?);
+                    }
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(AuditLog { path, entries })
+    }
+
+    pub fn record(
+        &mut self,
+        actor: Uuid,
+        action: AuditAction,
+        reason: Option<String>,
+    ) -> Result<()> {
+        let entry = AuditEntry {
+            time: unix_secs(SystemTime::now()),
+            actor,
+            action,
+            reason,
+        };
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Returns the matching entries, newest first.
+    pub fn query(&self, filter: &AuditFilter) -> Vec<AuditEntry> {
+        let limit = filter.limit.unwrap_or(100).min(Self::MAX_QUERY);
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| filter.matches(entry))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
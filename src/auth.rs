@@ -2,52 +2,456 @@ use crate::error::*;
 use log::*;
 
 use actix_web::{client::Client, http::StatusCode};
-use futures::Future;
-use serde::{de::IgnoredAny, Deserialize, Serialize};
+use futures::{future, unsync::oneshot, Future};
+use serde::{Deserialize, Serialize};
+use tokio_timer::Delay;
 use url::Url;
 
-use crate::config::AuthConfig;
-use jsonwebtoken::{Header, Validation};
+use crate::config::{AuthConfig, MojangConfig, PermissionConfig};
+use crate::keyset::Keyset;
+use crate::moderation::{Moderation, Role};
+use crate::revocation::Revocations;
+use crate::time::{unix_millis, unix_secs};
+use jsonwebtoken::{errors::ErrorKind, Algorithm, Validation};
+use ring::signature;
 use std::{
-    fs,
-    time::{Duration, SystemTime},
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fs, io,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
+use untrusted::{EndOfInput, Input, Reader};
 use uuid::Uuid;
 
-pub fn authenticate(
-    username: &str,
-    server_id: &str,
-) -> Result<impl Future<Item = AuthInfo, Error = Error>> {
-    let mut url =
-        Url::parse("https://sessionserver.mojang.com/session/minecraft/hasJoined").unwrap();
-    url.query_pairs_mut()
-        .append_pair("username", username)
-        .append_pair("serverId", server_id);
-
-    Ok(Client::new()
-        .get(url.as_str())
+/// The Mojang session server and the Minecraft services profile endpoint, or compatible ones.
+///
+/// Only a limited amount of requests is sent at once, further requests are queued.
+/// Successful profile lookups are cached for a short time.
+///
+/// `hasJoined` lookups are never cached: every connection joins with a new random server id,
+/// so a reconnecting client can't reuse the result of its previous connection.
+/// Reconnects are cheap only if the client resumes its session instead of logging in again.
+pub struct SessionServer {
+    url: Url,
+    profile_url: Url,
+    timeout: Duration,
+    retries: u32,
+    retry_delay: Duration,
+    signature_key: Option<Arc<Vec<u8>>>,
+    max_concurrent_requests: usize,
+    max_queued_requests: usize,
+    cache_duration: Duration,
+    upstream: Rc<RefCell<Upstream>>,
+}
+
+/// Statistics about the requests to the session server.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuthStats {
+    /// The requests which were sent to the session server.
+    pub requests: u64,
+    /// The lookups which were answered from the cache.
+    pub cache_hits: u64,
+    /// The requests which failed because the session server could not be reached
+    /// or responded with a server error.
+    pub failures: u64,
+    /// The requests which were rate limited by the session server.
+    pub rate_limited: u64,
+    /// The lookups which were rejected because the queue was full.
+    pub rejected: u64,
+    /// The requests which are currently running.
+    pub active: usize,
+    /// The requests which are currently waiting in the queue.
+    pub queued: usize,
+}
+
+/// The state shared by all requests to the session server.
+#[derive(Default)]
+struct Upstream {
+    active: usize,
+    queue: VecDeque<oneshot::Sender<()>>,
+    cache: HashMap<String, (Instant, AuthInfo)>,
+    stats: AuthStats,
+    /// The stats when they were last logged.
+    logged: AuthStats,
+}
+
+/// Allows sending one request to the session server.
+/// When dropped, the next queued request may be sent.
+struct Permit(Rc<RefCell<Upstream>>);
+
+impl Permit {
+    fn acquire(
+        upstream: &Rc<RefCell<Upstream>>,
+        max_concurrent_requests: usize,
+        max_queued_requests: usize,
+    ) -> Box<dyn Future<Item = Permit, Error = Error>> {
+        let mut state = upstream.borrow_mut();
+        if state.active < max_concurrent_requests {
+            state.active += 1;
+            return Box::new(future::ok(Permit(upstream.clone())));
+        }
+        if state.queue.len() >= max_queued_requests {
+            state.stats.rejected += 1;
+            return Box::new(future::err(ClientError::AuthUnavailable.into()));
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        state.queue.push_back(sender);
+        let upstream = upstream.clone();
+        Box::new(
+            receiver
+                .map(move |()| Permit(upstream))
+                .map_err(|_| ClientError::AuthUnavailable.into()),
+        )
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.0.borrow_mut();
+        // hand the permit over to the next request still waiting
+        while let Some(sender) = state.queue.pop_front() {
+            if sender.send(()).is_ok() {
+                return;
+            }
+        }
+        state.active -= 1;
+    }
+}
+
+impl SessionServer {
+    pub fn new(cfg: &MojangConfig) -> Result<SessionServer> {
+        let parse = |url: &str| {
+            Url::parse(url).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+        };
+        let url = parse(&cfg.session_server)?;
+        let profile_url = parse(&cfg.profile_endpoint)?;
+        let signature_key = match &cfg.signature_key {
+            Some(path) => {
+                let key = rsa_public_key(&fs::read(path)?).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "the signature key is not a DER encoded RSA public key",
+                    )
+                })?;
+                Some(Arc::new(key))
+            }
+            None => None,
+        };
+        if cfg.allow_mojang && signature_key.is_none() {
+            warn!(
+                "`mojang.signature_key` is not set, \
+                 the profiles returned by the session server are not verified."
+            );
+        }
+
+        Ok(SessionServer {
+            url,
+            profile_url,
+            timeout: *cfg.timeout,
+            retries: cfg.retries,
+            retry_delay: *cfg.retry_delay,
+            signature_key,
+            max_concurrent_requests: cfg.max_concurrent_requests,
+            max_queued_requests: cfg.max_queued_requests,
+            cache_duration: *cfg.cache_duration,
+            upstream: Rc::default(),
+        })
+    }
+
+    pub fn stats(&self) -> AuthStats {
+        let upstream = self.upstream.borrow();
+        AuthStats {
+            active: upstream.active,
+            queued: upstream.queue.len(),
+            ..upstream.stats.clone()
+        }
+    }
+
+    /// Logs how many requests were sent and how many failed since this was last called.
+    pub fn log_stats(&self) {
+        let mut upstream = self.upstream.borrow_mut();
+        let (stats, logged) = (&upstream.stats, &upstream.logged);
+        let requests = stats.requests - logged.requests;
+        let cache_hits = stats.cache_hits - logged.cache_hits;
+        let failures = stats.failures - logged.failures;
+        let rate_limited = stats.rate_limited - logged.rate_limited;
+        let rejected = stats.rejected - logged.rejected;
+
+        if failures > 0 || rate_limited > 0 || rejected > 0 {
+            warn!(
+                "Session server: {} requests, {} cache hits, {} failed, {} rate limited, \
+                 {} rejected because the queue was full.",
+                requests, cache_hits, failures, rate_limited, rejected
+            );
+        } else if requests > 0 || cache_hits > 0 {
+            info!(
+                "Session server: {} requests, {} cache hits.",
+                requests, cache_hits
+            );
+        }
+        upstream.logged = upstream.stats.clone();
+    }
+
+    /// Asks the session server whether `username` has joined `server_id`.
+    ///
+    /// Requests failing because of network or server errors are retried,
+    /// if they still fail `ClientError::AuthUnavailable` is returned.
+    /// Malformed responses and profiles with invalid signatures are treated as failed logins.
+    pub fn has_joined(
+        &self,
+        username: &str,
+        server_id: &str,
+    ) -> Box<dyn Future<Item = AuthInfo, Error = Error>> {
+        let mut url = self.url.clone();
+        url.query_pairs_mut()
+            .append_pair("username", username)
+            .append_pair("serverId", server_id);
+        if self.signature_key.is_some() {
+            url.query_pairs_mut().append_pair("unsigned", "false");
+        }
+
+        let signature_key = self.signature_key.clone();
+        Box::new(self.fetch(None, url, None).and_then(move |info| {
+            if let Some(key) = signature_key {
+                info.verify_properties(&key)?;
+            }
+            Ok(info)
+        }))
+    }
+
+    /// Looks up the profile owning the Minecraft services `access_token`.
+    ///
+    /// Requests are retried like in [`SessionServer::has_joined`].
+    pub fn profile(&self, access_token: &str) -> impl Future<Item = AuthInfo, Error = Error> {
+        self.fetch(
+            Some(format!("profile:{}", access_token)),
+            self.profile_url.clone(),
+            Some(access_token.to_owned()),
+        )
+    }
+
+    /// Requests a profile, unless it is cached with `key`.
+    /// Without a `key`, the profile is neither looked up in nor added to the cache.
+    fn fetch(
+        &self,
+        key: Option<String>,
+        url: Url,
+        access_token: Option<String>,
+    ) -> Box<dyn Future<Item = AuthInfo, Error = Error>> {
+        let now = Instant::now();
+        let cache_duration = self.cache_duration;
+        if let Some(key) = &key {
+            let mut upstream = self.upstream.borrow_mut();
+            let cached = upstream
+                .cache
+                .get(key)
+                .filter(|(time, _)| now.duration_since(*time) < cache_duration)
+                .map(|(_, info)| info.clone());
+            if let Some(info) = cached {
+                upstream.stats.cache_hits += 1;
+                return Box::new(future::ok(info));
+            }
+        }
+
+        let upstream = self.upstream.clone();
+        let (timeout, retries, retry_delay) = (self.timeout, self.retries, self.retry_delay);
+        Box::new(
+            Permit::acquire(
+                &self.upstream,
+                self.max_concurrent_requests,
+                self.max_queued_requests,
+            )
+            .and_then(move |permit| {
+                upstream.borrow_mut().stats.requests += 1;
+                request_profile(url, access_token, timeout, retries, retry_delay).then(move |res| {
+                    drop(permit);
+
+                    let mut upstream = upstream.borrow_mut();
+                    match res {
+                        Ok(info) => {
+                            let now = Instant::now();
+                            upstream
+                                .cache
+                                .retain(|_, (time, _)| now.duration_since(*time) < cache_duration);
+                            if let Some(key) = key {
+                                upstream.cache.insert(key, (now, info.clone()));
+                            }
+                            Ok(info)
+                        }
+                        Err(err @ Error::Actix { .. }) => {
+                            warn!("Session server is unavailable: {}", err);
+                            upstream.stats.failures += 1;
+                            Err(ClientError::AuthUnavailable.into())
+                        }
+                        Err(Error::AxoChat {
+                            source: ClientError::AuthUnavailable,
+                        }) => {
+                            upstream.stats.rate_limited += 1;
+                            Err(ClientError::AuthUnavailable.into())
+                        }
+                        Err(err) => Err(err),
+                    }
+                })
+            }),
+        )
+    }
+}
+
+/// Requests a profile, retrying after `retry_delay` and then twice as long for every retry.
+fn request_profile(
+    url: Url,
+    access_token: Option<String>,
+    timeout: Duration,
+    retries: u32,
+    retry_delay: Duration,
+) -> Box<dyn Future<Item = AuthInfo, Error = Error>> {
+    let mut request = Client::build().timeout(timeout).finish().get(url.as_str());
+    if let Some(access_token) = &access_token {
+        request = request.bearer_auth(access_token);
+    }
+
+    let profile = request
         .send()
         .map_err(|err| Error::Actix { source: err.into() })
         .and_then(|response| {
-            if response.status() == StatusCode::OK {
+            let status = response.status();
+            if status == StatusCode::OK {
                 Ok(response)
+            } else if status == StatusCode::TOO_MANY_REQUESTS {
+                warn!("Session server is rate limiting requests.");
+                Err(ClientError::AuthUnavailable.into())
+            } else if status.is_server_error() {
+                Err(Error::Actix {
+                    source: actix_web::error::ErrorBadGateway(format!(
+                        "server responded with {}",
+                        status
+                    )),
+                })
             } else {
-                debug!("Login status-code is {}", response.status());
+                debug!("Login status-code is {}", status);
                 Err(ClientError::LoginFailed.into())
             }
         })
         .and_then(|mut response| {
-            response
-                .json()
-                .map_err(|err| Error::Actix { source: err.into() })
-        }))
+            response.json().map_err(|err| {
+                warn!("Received malformed profile: {}", err);
+                Error::from(ClientError::LoginFailed)
+            })
+        });
+
+    Box::new(profile.or_else(
+        move |err| -> Box<dyn Future<Item = AuthInfo, Error = Error>> {
+            match err {
+                Error::Actix { .. } if retries > 0 => {
+                    debug!(
+                        "Retrying request to `{}` in {:?}: {}",
+                        url, retry_delay, err
+                    );
+                    Box::new(Delay::new(Instant::now() + retry_delay).then(move |_| {
+                        request_profile(url, access_token, timeout, retries - 1, retry_delay * 2)
+                    }))
+                }
+                err => Box::new(future::err(err)),
+            }
+        },
+    ))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AuthInfo {
-    pub id: String,
+    pub id: Uuid,
     pub name: String,
-    properties: IgnoredAny,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+impl AuthInfo {
+    /// Checks that the profile has properties and all of them are signed with `key`.
+    fn verify_properties(&self, key: &[u8]) -> Result<()> {
+        if self.properties.is_empty() {
+            warn!("Profile of `{}` has no signed properties.", self.name);
+            return Err(ClientError::LoginFailed.into());
+        }
+
+        for property in &self.properties {
+            let valid = property
+                .signature
+                .as_ref()
+                .and_then(|signature| base64::decode(signature).ok())
+                .is_some_and(|signature| {
+                    signature::verify(
+                        &signature::RSA_PKCS1_2048_8192_SHA1,
+                        Input::from(key),
+                        Input::from(property.value.as_bytes()),
+                        Input::from(&signature),
+                    )
+                    .is_ok()
+                });
+            if !valid {
+                warn!(
+                    "Property `{}` of `{}` has an invalid signature.",
+                    property.name, self.name
+                );
+                return Err(ClientError::LoginFailed.into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Extracts the `RSAPublicKey` from a DER encoded `SubjectPublicKeyInfo`,
+/// which is the format of authlib's key.
+/// Keys which already are an `RSAPublicKey` are returned unchanged.
+fn rsa_public_key(key: &[u8]) -> Option<Vec<u8>> {
+    const INTEGER: u8 = 0x02;
+    const BIT_STRING: u8 = 0x03;
+    const SEQUENCE: u8 = 0x30;
+
+    fn read_der<'a>(
+        reader: &mut Reader<'a>,
+        tag: u8,
+    ) -> std::result::Result<Input<'a>, EndOfInput> {
+        if reader.read_byte()? != tag {
+            return Err(EndOfInput);
+        }
+        let length = match reader.read_byte()? {
+            length if length < 0x80 => usize::from(length),
+            0x81 => usize::from(reader.read_byte()?),
+            0x82 => usize::from(reader.read_byte()?) << 8 | usize::from(reader.read_byte()?),
+            _ => return Err(EndOfInput),
+        };
+        reader.skip_and_get_input(length)
+    }
+
+    Input::from(key)
+        .read_all(EndOfInput, |reader| {
+            read_der(reader, SEQUENCE)?.read_all(EndOfInput, |sequence| {
+                if sequence.peek(INTEGER) {
+                    sequence.skip_to_end();
+                    return Ok(key.to_vec());
+                }
+                read_der(sequence, SEQUENCE)?;
+                read_der(sequence, BIT_STRING)?.read_all(EndOfInput, |bits| {
+                    // the key always consists of whole bytes
+                    if bits.read_byte()? != 0 {
+                        return Err(EndOfInput);
+                    }
+                    Ok(bits.skip_to_end().as_slice_less_safe().to_vec())
+                })
+            })
+        })
+        .ok()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Property {
+    name: String,
+    value: String,
+    signature: Option<String>,
 }
 
 pub fn encode_sha1_bytes(bytes: &[u8; 20]) -> String {
@@ -82,50 +486,259 @@ pub fn encode_sha1_bytes(bytes: &[u8; 20]) -> String {
     buf
 }
 
+/// Issues and validates JWTs.
+///
+/// It is shared between the chat server and the JWT login provider,
+/// so the mutable parts are kept in cells.
 pub struct Authenticator {
-    validation: Validation,
-    header: Header,
-    key: Vec<u8>,
+    keys: RefCell<Keyset>,
     valid_time: Duration,
+    refresh_valid_time: Duration,
+    revocations: RefCell<Revocations>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    permissions: PermissionConfig,
 }
 
 impl Authenticator {
     pub fn new(cfg: &AuthConfig) -> Result<Authenticator> {
+        let keys = match (&cfg.keys, &cfg.key_file) {
+            (Some(keys), _) => Keyset::from_file(keys.clone())?,
+            (None, Some(key_file)) => Keyset::single(cfg.algorithm, key_file)?,
+            (None, None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "either `keys` or `key_file` is required",
+                )
+                .into());
+            }
+        };
+
         Ok(Authenticator {
-            validation: Validation::new(cfg.algorithm),
-            header: Header::new(cfg.algorithm),
-            key: fs::read(&cfg.key_file)?,
+            keys: RefCell::new(keys),
             valid_time: *cfg.valid_time,
+            refresh_valid_time: *cfg.refresh_valid_time,
+            revocations: RefCell::new(Revocations::new(cfg.revocations.clone())?),
+            issuer: cfg.issuer.clone(),
+            audience: cfg.audience.clone(),
+            permissions: cfg.permissions.clone(),
         })
     }
 
+    /// Validates an access token.
     pub fn auth(&self, token: &str) -> Result<UserInfo> {
-        match jsonwebtoken::decode::<Claims>(token, &self.key, &self.validation) {
-            Ok(data) => Ok(data.claims.user),
-            Err(err) => Err(err.into()),
+        self.validate(token, TokenKind::Access)
+            .map(|claims| claims.user)
+    }
+
+    /// Issues a token for `info`, carrying the permissions of `role`.
+    pub fn new_token(&self, info: UserInfo, role: Role) -> Result<String> {
+        self.encode(info, role, TokenKind::Access, self.valid_time)
+    }
+
+    pub fn new_refresh_token(&self, info: UserInfo, role: Role) -> Result<String> {
+        self.encode(info, role, TokenKind::Refresh, self.refresh_valid_time)
+    }
+
+    /// Exchanges a refresh token for a new access token and a new refresh token.
+    /// Every refresh token can only be used once.
+    ///
+    /// Banned users can't refresh their tokens.
+    /// The role is looked up again, as it might have changed since the refresh token was issued.
+    pub fn refresh(
+        &self,
+        refresh_token: &str,
+        moderation: &Moderation,
+    ) -> Result<(UserInfo, String, String)> {
+        let claims = self.validate(refresh_token, TokenKind::Refresh)?;
+        if moderation.is_banned(&claims.user.uuid) {
+            return Err(ClientError::Banned.into());
+        }
+        self.revocations
+            .borrow_mut()
+            .revoke_token(claims.jti, claims.exp)?;
+
+        let role = moderation.role(&claims.user.uuid);
+        let token = self.new_token(claims.user.clone(), role)?;
+        let refresh_token = self.new_refresh_token(claims.user.clone(), role)?;
+        Ok((claims.user, token, refresh_token))
+    }
+
+    /// Reloads the keys if the key file was modified.
+    pub fn reload_keys(&self) {
+        self.keys.borrow_mut().reload_if_modified();
+    }
+
+    /// Revokes every token issued to `user` until now.
+    pub fn revoke(&self, user: Uuid) -> Result<()> {
+        self.revocations.borrow_mut().revoke_user(user)
+    }
+
+    fn validate(&self, token: &str, kind: TokenKind) -> Result<Claims> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let mut result = Err(ErrorKind::InvalidSignature.into());
+        for (algorithm, key) in self.keys.borrow().verification_keys(&header) {
+            result = jsonwebtoken::decode::<Claims>(token, key, &self.validation(algorithm));
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        let claims = result?.claims;
+        if claims.kind != kind
+            || self.revocations.borrow().is_revoked(
+                &claims.user.uuid,
+                &claims.jti,
+                claims.issued_millis(),
+            )
+        {
+            return Err(ClientError::LoginFailed.into());
+        }
+
+        Ok(claims)
+    }
+
+    fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.iss = self.issuer.clone();
+        if let Some(audience) = &self.audience {
+            validation.set_audience(audience);
         }
+        validation
     }
 
-    pub fn new_token(&self, info: UserInfo) -> Result<String> {
-        let unix_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("system time is somehow before the unix epoch");
+    fn encode(
+        &self,
+        info: UserInfo,
+        role: Role,
+        kind: TokenKind,
+        valid_time: Duration,
+    ) -> Result<String> {
+        let now = SystemTime::now();
         let claims = Claims {
-            exp: (unix_time + self.valid_time).as_secs(),
+            exp: unix_secs(now + valid_time),
+            iat: unix_secs(now),
+            iat_millis: unix_millis(now),
+            jti: format!("{:032x}", rand::random::<u128>()),
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            kind,
+            role,
+            permissions: self.permissions.of(role),
             user: info,
         };
-        jsonwebtoken::encode(&self.header, &claims, &self.key).map_err(|err| err.into())
+        let keys = self.keys.borrow();
+        let (header, key) = keys.signing_key();
+        jsonwebtoken::encode(&header, &claims, key).map_err(|err| err.into())
     }
 }
 
+/// What a token may be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    /// The token can be used to log in.
+    #[default]
+    Access,
+    /// The token can only be exchanged for new tokens.
+    Refresh,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     exp: u64,
+    #[serde(default)]
+    iat: u64,
+    /// The time the token was issued in milliseconds, so that tokens issued right after
+    /// a revocation are not revoked as well.
+    #[serde(default)]
+    iat_millis: u64,
+    #[serde(default)]
+    jti: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aud: Option<String>,
+    #[serde(default)]
+    kind: TokenKind,
+    /// The role of the user when the token was issued.
+    /// It is meant for other services; the chat server always uses its current role.
+    #[serde(default)]
+    role: Role,
+    #[serde(default)]
+    permissions: Vec<String>,
     user: UserInfo,
 }
 
+impl Claims {
+    /// Returns the time the token was issued in milliseconds since the unix epoch.
+    /// Tokens issued before `iat_millis` existed are treated as issued at the end of `iat`.
+    fn issued_millis(&self) -> u64 {
+        if self.iat_millis != 0 {
+            self.iat_millis
+        } else {
+            self.iat * 1000 + 999
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInfo {
     pub name: String,
     pub uuid: Uuid,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `SubjectPublicKeyInfo`, like authlib's `yggdrasil_session_pubkey.der`.
+    const KEY: &str = "MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAshR7VMAAo+4CxjpL+2Qga9Ix8kWlH2b2QPHze1yC9FVfbTRH8Z9+DVjJU10T8guPyEmyHdYsfltjArmYR6vXMwCv5QhOdoBUSvq3wl6bIkjd+lJZi1iVO49UHfsRuawTC1i+8qAIwzXHGCqtH0JsqJvfqRFXZkZU5sfFI0A6bICRBqKsjjxde/1r+CyQKPISQ8FRVszvnWonvFDdhYH/044kcEmVB0jqN09D6eRI3b+3V9xW63yUuquybc6WBaSQL+U/B96zkaJPSVaJis4VnDrUybvXytAPLrZ5I7q6l9z0Sucn1bx4uvj/xdcKgHedh1Fmk5FlPuz4BVlZl3OguQIDAQAB";
+    /// A `textures` property signed with the key above using SHA1 like Mojang's session server.
+    const TEXTURES: &str = "eyJ0aW1lc3RhbXAiOjE3MDAwMDAwMDAwMDAsInByb2ZpbGVJZCI6IjA2OWE3OWY0NDRlOTQ3MjZhNWJlZmNhOTBlMzhhYWY1IiwicHJvZmlsZU5hbWUiOiJOb3RjaCIsInNpZ25hdHVyZVJlcXVpcmVkIjp0cnVlLCJ0ZXh0dXJlcyI6eyJTS0lOIjp7InVybCI6Imh0dHA6Ly90ZXh0dXJlcy5taW5lY3JhZnQubmV0L3RleHR1cmUvMjkyMDA5YTQ5MjViNThmMDJjNzdkYWRjM2VjZWYwN2VhNGM3NDcyZjY0ZTBmZGMzMmNlNTUyMjQ4OTM2MjY4MCJ9fX0=";
+    const SIGNATURE: &str = "Ogo+DiJonO7b5eOhJ+jjty1BFH1dKxfSGEiAKOztG2bg2CxTOQglJs/QIUb6Gqp9g0vixhxWLnW+XFhwQGIo12mw6GCu/iWbXq6Me3gp6HT6UBYr7RQnaQ384XK/MeghNB/Enn0C3bArE9wU9B5z3m/xQe1I1daTGWrd6IaEmrRRGX+6FRhh+zugA1YLO1TzjBUTQ64bNsD8JNe7mFbR77ws/V3subKbBevQn7W4fTPfjxksxwfMpAm10ljI/+iVYaFHEkoqhEbRL2sa7iQPhROQLlHKo2ZQZMvpcU3nSCss8s7LTHPDNa+ZI1s7RaWDajKEm3K06jjtT2wPV4in2w==";
+
+    fn profile(value: &str, signature: Option<&str>) -> AuthInfo {
+        AuthInfo {
+            id: "069a79f4-44e9-4726-a5be-fca90e38aaf5".parse().unwrap(),
+            name: String::from("Notch"),
+            properties: vec![Property {
+                name: String::from("textures"),
+                value: value.to_owned(),
+                signature: signature.map(str::to_owned),
+            }],
+        }
+    }
+
+    #[test]
+    fn signed_textures() {
+        let key = rsa_public_key(&base64::decode(KEY).unwrap()).unwrap();
+        assert!(profile(TEXTURES, Some(SIGNATURE))
+            .verify_properties(&key)
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_signatures() {
+        let key = rsa_public_key(&base64::decode(KEY).unwrap()).unwrap();
+        let tampered = &TEXTURES[1..];
+        assert!(profile(tampered, Some(SIGNATURE))
+            .verify_properties(&key)
+            .is_err());
+        assert!(profile(TEXTURES, None).verify_properties(&key).is_err());
+        // an empty profile could not prove anything
+        let mut empty = profile(TEXTURES, Some(SIGNATURE));
+        empty.properties.clear();
+        assert!(empty.verify_properties(&key).is_err());
+    }
+
+    #[test]
+    fn key_formats() {
+        let spki = base64::decode(KEY).unwrap();
+        let rsa_key = rsa_public_key(&spki).unwrap();
+        assert_ne!(rsa_key, spki);
+        assert_eq!(rsa_public_key(&rsa_key), Some(rsa_key.clone()));
+        assert_eq!(rsa_public_key(&spki[..spki.len() - 1]), None);
+        assert_eq!(rsa_public_key(b"not a key"), None);
+    }
+}
@@ -1,35 +1,67 @@
+use crate::error::*;
 use log::*;
 
-use super::{ChatServer, ClientPacket, InternalId, SessionState};
+use super::{session::Terminate, ChatServer, ClientPacket, InternalId, SessionState};
+use crate::limit::PacketLimiter;
 use actix::*;
+use std::{net::IpAddr, time::SystemTime};
 
 #[derive(Message)]
-#[rtype(InternalId)]
+#[rtype(result = "std::result::Result<InternalId, ClientError>")]
 pub(super) struct Connect {
     addr: Recipient<ClientPacket>,
+    close: Recipient<Terminate>,
+    ip: Option<IpAddr>,
 }
 
 impl Connect {
-    pub fn new(addr: Recipient<ClientPacket>) -> Connect {
-        Connect { addr }
+    pub fn new(
+        addr: Recipient<ClientPacket>,
+        close: Recipient<Terminate>,
+        ip: Option<IpAddr>,
+    ) -> Connect {
+        Connect { addr, close, ip }
     }
 }
 
 impl Handler<Connect> for ChatServer {
-    type Result = InternalId;
+    type Result = std::result::Result<InternalId, ClientError>;
+
+    fn handle(&mut self, msg: Connect, _ctx: &mut Context<Self>) -> Self::Result {
+        let limits = &self.config.limits;
+        if self.connections.len() >= limits.max_connections {
+            info!("Refused connection from {:?}: server is full.", msg.ip);
+            return Err(ClientError::TooManyConnections);
+        }
+        if let Some(ip) = msg.ip {
+            let count = self.ip_connections.entry(ip).or_insert(0);
+            if *count >= limits.max_connections_per_ip {
+                info!("Refused connection from `{}`: too many connections.", ip);
+                return Err(ClientError::TooManyConnections);
+            }
+            *count += 1;
+        }
 
-    fn handle(&mut self, msg: Connect, _ctx: &mut Context<Self>) -> InternalId {
         self.current_internal_user_id += 1;
         let id = InternalId::new(self.current_internal_user_id);
         self.connections.insert(
             id,
             SessionState {
                 addr: msg.addr.clone(),
+                close: msg.close,
+                ip: msg.ip,
+                connected: SystemTime::now(),
                 session_hash: None,
                 user: None,
+                auth_method: None,
+                resume_token: None,
+                packet_limiter: PacketLimiter::new(
+                    limits.max_unauthenticated_packets,
+                    *limits.unauthenticated_duration,
+                ),
             },
         );
-        debug!("User `{}` joined the chat.", id);
-        id
+        debug!("User `{}` joined the chat from {:?}.", id, msg.ip);
+        Ok(id)
     }
 }
@@ -0,0 +1,25 @@
+use super::{ChatServer, ClientPacket};
+use crate::audit::{AuditAction, AuditFilter};
+use crate::chat::InternalId;
+
+use log::*;
+use uuid::Uuid;
+
+impl ChatServer {
+    pub(super) fn handle_request_audit_log(&mut self, user_id: InternalId, filter: &AuditFilter) {
+        if let Some(session) = self.admin_session(user_id) {
+            let entries = self.audit.query(filter);
+            if let Err(err) = session.addr.do_send(ClientPacket::AuditLog { entries }) {
+                warn!("Could not send audit log to user `{}`: {}", user_id, err);
+            }
+        }
+    }
+
+    /// Records a moderation action in the audit log.
+    pub(super) fn audit(&mut self, actor: Uuid, action: AuditAction, reason: Option<String>) {
+        info!("Moderation action by `{}`: {:?}", actor, action);
+        if let Err(err) = self.audit.record(actor, action, reason) {
+            warn!("Could not write to audit log: {}", err);
+        }
+    }
+}
@@ -1,20 +1,51 @@
 use super::{ChatServer, ClientPacket};
+use crate::audit::AuditAction;
 use crate::chat::{InternalId, SuccessReason};
+use crate::moderation::BanTarget;
 
 use crate::error::*;
 use log::*;
 use uuid::Uuid;
 
+#[derive(Clone, Copy)]
+enum BanAction {
+    Ban,
+    ShadowBan,
+    Unban,
+}
+
 impl ChatServer {
-    pub(super) fn ban_user(&mut self, user_id: InternalId, to_ban: &Uuid) {
-        self.handle_user(user_id, to_ban, true);
+    pub(super) fn ban_user(
+        &mut self,
+        user_id: InternalId,
+        to_ban: &Uuid,
+        shadow: bool,
+        reason: Option<String>,
+    ) {
+        let action = if shadow {
+            BanAction::ShadowBan
+        } else {
+            BanAction::Ban
+        };
+        self.handle_user(user_id, to_ban, action, reason);
     }
 
-    pub(super) fn unban_user(&mut self, user_id: InternalId, to_unban: &Uuid) {
-        self.handle_user(user_id, to_unban, false);
+    pub(super) fn unban_user(
+        &mut self,
+        user_id: InternalId,
+        to_unban: &Uuid,
+        reason: Option<String>,
+    ) {
+        self.handle_user(user_id, to_unban, BanAction::Unban, reason);
     }
 
-    fn handle_user(&mut self, user_id: InternalId, receiver: &Uuid, ban: bool) {
+    fn handle_user(
+        &mut self,
+        user_id: InternalId,
+        receiver: &Uuid,
+        action: BanAction,
+        reason: Option<String>,
+    ) {
         let session = self
             .connections
             .get(&user_id)
@@ -31,21 +62,34 @@ impl ChatServer {
                 return;
             }
 
-            let res = if ban {
-                self.moderation.ban(receiver)
-            } else {
-                self.moderation.unban(receiver)
+            let res = match action {
+                BanAction::Ban => self.moderation.ban(receiver),
+                BanAction::ShadowBan => self.moderation.shadow_ban(receiver),
+                BanAction::Unban => self.moderation.unban(receiver),
             };
             match res {
                 Ok(()) => {
-                    let reason = if ban {
-                        info!("User `{}` banned.", receiver);
-                        SuccessReason::Ban
-                    } else {
-                        info!("User `{}` unbanned.", receiver);
-                        SuccessReason::Unban
+                    let (success, action) = match action {
+                        BanAction::Ban => {
+                            info!("User `{}` banned.", receiver);
+                            (SuccessReason::Ban, AuditAction::Ban { user: *receiver })
+                        }
+                        BanAction::ShadowBan => {
+                            info!("User `{}` shadow banned.", receiver);
+                            (
+                                SuccessReason::ShadowBan,
+                                AuditAction::ShadowBan { user: *receiver },
+                            )
+                        }
+                        BanAction::Unban => {
+                            info!("User `{}` unbanned.", receiver);
+                            (SuccessReason::Unban, AuditAction::Unban { user: *receiver })
+                        }
                     };
-                    let _ = session.addr.do_send(ClientPacket::Success { reason });
+                    let actor = info.uuid;
+                    let addr = session.addr.clone();
+                    self.audit(actor, action, reason);
+                    let _ = addr.do_send(ClientPacket::Success { reason: success });
                 }
                 Err(Error::AxoChat { source }) => {
                     info!("Could not (un-)ban user `{}`: {}", receiver, source);
@@ -75,4 +119,98 @@ impl ChatServer {
             return;
         }
     }
+
+    pub(super) fn ban_target(
+        &mut self,
+        user_id: InternalId,
+        target: BanTarget,
+        reason: Option<String>,
+    ) {
+        let (addr, actor) = match self.moderator_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+
+        // link the users which are currently connected using the target
+        let mut users = Vec::new();
+        for session in self.connections.values() {
+            if let Some(user) = &session.user {
+                if target.matches(session.ip, user.identifier.as_deref()) {
+                    users.push(user.uuid);
+                }
+            }
+        }
+        if users.iter().any(|user| self.moderation.is_moderator(user)) {
+            info!(
+                "`{}` tried to ban {:?} used by a moderator",
+                user_id, target
+            );
+            addr.do_send(ClientPacket::Error {
+                message: ClientError::NotPermitted,
+            })
+            .ok();
+            return;
+        }
+
+        match self.moderation.ban_target(target.clone(), users) {
+            Ok(()) => {
+                info!("{:?} banned.", target);
+                self.audit(actor, AuditAction::BanTarget { target }, reason);
+                addr.do_send(ClientPacket::Success {
+                    reason: SuccessReason::Ban,
+                })
+                .ok();
+            }
+            Err(err) => {
+                warn!("Could not ban {:?}: {}", target, err);
+                addr.do_send(ClientPacket::Error {
+                    message: ClientError::Internal,
+                })
+                .ok();
+            }
+        }
+    }
+
+    pub(super) fn unban_target(
+        &mut self,
+        user_id: InternalId,
+        target: BanTarget,
+        reason: Option<String>,
+    ) {
+        let (addr, actor) = match self.moderator_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+
+        match self.moderation.unban_target(&target) {
+            Ok(()) => {
+                info!("{:?} unbanned.", target);
+                self.audit(actor, AuditAction::UnbanTarget { target }, reason);
+                addr.do_send(ClientPacket::Success {
+                    reason: SuccessReason::Unban,
+                })
+                .ok();
+            }
+            Err(Error::AxoChat { source }) => {
+                info!("Could not unban {:?}: {}", target, source);
+                addr.do_send(ClientPacket::Error { message: source }).ok();
+            }
+            Err(err) => {
+                warn!("Could not unban {:?}: {}", target, err);
+                addr.do_send(ClientPacket::Error {
+                    message: ClientError::Internal,
+                })
+                .ok();
+            }
+        }
+    }
+
+    pub(super) fn handle_list_target_bans(&mut self, user_id: InternalId) {
+        if let Some(session) = self.moderator_session(user_id) {
+            let bans = self.moderation.target_bans().to_vec();
+            if let Err(err) = session.addr.do_send(ClientPacket::TargetBans { bans }) {
+                warn!("Could not send bans to user `{}`: {}", user_id, err);
+            }
+        }
+    }
 }
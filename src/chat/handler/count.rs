@@ -34,4 +34,21 @@ impl ChatServer {
             });
         }
     }
+
+    pub(super) fn send_auth_stats(&mut self, user_id: InternalId) {
+        let session = match self.moderator_session(user_id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        if let Err(err) = session
+            .addr
+            .do_send(ClientPacket::AuthStats(self.session_server.stats()))
+        {
+            warn!(
+                "Could not send authentication stats to user `{}`: {}",
+                user_id, err
+            );
+        }
+    }
 }
@@ -0,0 +1,195 @@
+use super::{ChatServer, ClientPacket};
+use crate::chat::{FriendInfo, InternalId, SuccessReason};
+use crate::preferences::Privacy;
+
+use crate::error::*;
+use log::*;
+use uuid::Uuid;
+
+impl ChatServer {
+    pub(super) fn handle_send_friend_request(&mut self, user_id: InternalId, user: &str) {
+        let (addr, uuid) = match self.logged_in_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+        // only users who logged in before can have friends
+        let receiver = match self.resolve_user(user) {
+            Some(receiver) if self.directory.first_seen(&receiver).is_some() => receiver,
+            _ => {
+                addr.do_send(ClientPacket::Error {
+                    message: ClientError::UnknownUser,
+                })
+                .ok();
+                return;
+            }
+        };
+        let requested = self.friends.incoming(&uuid).any(|from| *from == receiver);
+        if !requested && !self.accepts_friend_requests(&receiver, &uuid) {
+            info!(
+                "`{}` does not accept friend requests from `{}`.",
+                receiver, uuid
+            );
+            addr.do_send(ClientPacket::Error {
+                message: ClientError::FriendRequestNotAccepted,
+            })
+            .ok();
+            return;
+        }
+
+        let max_pending = self.config.limits.max_pending_friend_requests;
+        let (limiter, friends) = (&mut self.friend_limiter, &mut self.friends);
+        let result = limiter
+            .check(&uuid)
+            .and_then(|()| friends.request(uuid, receiver, max_pending));
+        match result {
+            Ok(accepted) => {
+                if accepted {
+                    info!("`{}` and `{}` are now friends.", uuid, receiver);
+                    self.send_friend_status(&uuid, &receiver);
+                } else {
+                    info!("`{}` sent a friend request to `{}`.", uuid, receiver);
+                    let user = self.user_info(&uuid);
+                    self.send_to_user(&receiver, ClientPacket::FriendRequest { user });
+                }
+                addr.do_send(ClientPacket::Success {
+                    reason: SuccessReason::FriendRequest,
+                })
+                .ok();
+            }
+            Err(err) => send_error(&addr, uuid, err),
+        }
+    }
+
+    pub(super) fn handle_accept_friend_request(&mut self, user_id: InternalId, from: Uuid) {
+        let (addr, uuid) = match self.logged_in_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+
+        match self.friends.accept(uuid, from) {
+            Ok(()) => {
+                info!("`{}` and `{}` are now friends.", uuid, from);
+                self.send_friend_status(&uuid, &from);
+                addr.do_send(ClientPacket::Success {
+                    reason: SuccessReason::AcceptFriendRequest,
+                })
+                .ok();
+            }
+            Err(err) => send_error(&addr, uuid, err),
+        }
+    }
+
+    pub(super) fn handle_remove_friend(&mut self, user_id: InternalId, friend: Uuid) {
+        let (addr, uuid) = match self.logged_in_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+
+        match self.friends.remove(uuid, friend) {
+            Ok(()) => {
+                info!("`{}` removed `{}` as friend.", uuid, friend);
+                self.send_to_user(&friend, ClientPacket::FriendRemoved { user: uuid });
+                addr.do_send(ClientPacket::Success {
+                    reason: SuccessReason::RemoveFriend,
+                })
+                .ok();
+            }
+            Err(err) => send_error(&addr, uuid, err),
+        }
+    }
+
+    pub(super) fn handle_list_friends(&mut self, user_id: InternalId) {
+        let session = match self.logged_in_session(user_id) {
+            Some(session) => session,
+            None => return,
+        };
+        let uuid = session.user.as_ref().unwrap().uuid;
+
+        let friends = self
+            .friends
+            .friends(&uuid)
+            .map(|friend| FriendInfo {
+                user: self.user_info(friend),
+                online: self.users.get(friend).is_some(),
+            })
+            .collect();
+        let incoming = self
+            .friends
+            .incoming(&uuid)
+            .map(|user| self.user_info(user))
+            .collect();
+        let outgoing = self
+            .friends
+            .outgoing(&uuid)
+            .map(|user| self.user_info(user))
+            .collect();
+
+        let client_packet = ClientPacket::Friends {
+            friends,
+            incoming,
+            outgoing,
+        };
+        if let Err(err) = session.addr.do_send(client_packet) {
+            warn!("Could not send friends to user `{}`: {}", user_id, err);
+        }
+    }
+
+    /// Returns whether `receiver` accepts friend requests from `sender`.
+    /// Users who only accept private messages from friends still accept friend requests.
+    fn accepts_friend_requests(&self, receiver: &Uuid, sender: &Uuid) -> bool {
+        match self.preferences_of(receiver).privacy {
+            Privacy::Everyone | Privacy::FriendsOnly => true,
+            Privacy::ModeratorsOnly => self.moderation.is_moderator(sender),
+            Privacy::Nobody => false,
+        }
+    }
+
+    /// Tells the friends of a user that they came online, if this is their first connection.
+    /// If the user may still resume a session, their friends never saw them go offline.
+    pub(super) fn notify_login(&self, user_id: InternalId) {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        let uuid = session
+            .user
+            .as_ref()
+            .expect("user should be logged in")
+            .uuid;
+
+        let first = self
+            .users
+            .get(&uuid)
+            .is_some_and(|user| user.connections.len() == 1);
+        if first && !self.suspended.contains(&uuid) {
+            self.notify_friends(&uuid, true);
+        }
+    }
+
+    /// Tells two new friends about each other.
+    fn send_friend_status(&self, user: &Uuid, friend: &Uuid) {
+        for (user, friend) in &[(user, friend), (friend, user)] {
+            let client_packet = ClientPacket::FriendStatus {
+                user: self.user_info(friend),
+                online: self.users.get(friend).is_some(),
+            };
+            self.send_to_user(user, client_packet);
+        }
+    }
+}
+
+fn send_error(addr: &actix::Recipient<ClientPacket>, uuid: Uuid, err: Error) {
+    match err {
+        Error::AxoChat { source } => {
+            info!("Friend action of `{}` failed: {}", uuid, source);
+            addr.do_send(ClientPacket::Error { message: source }).ok();
+        }
+        err => {
+            warn!("Friend action of `{}` failed: {}", uuid, err);
+            addr.do_send(ClientPacket::Error {
+                message: ClientError::Internal,
+            })
+            .ok();
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use super::{ChatServer, ClientPacket};
+use crate::chat::{GroupId, InternalId};
+use crate::groups::Group;
+
+use crate::error::*;
+use actix::Recipient;
+use log::*;
+use uuid::Uuid;
+
+impl ChatServer {
+    pub(super) fn handle_create_group(
+        &mut self,
+        user_id: InternalId,
+        name: String,
+        members: Vec<Uuid>,
+    ) {
+        let (addr, uuid) = match self.logged_in_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+        if let Err(err) = self.validator.validate(&name) {
+            send_error(&addr, user_id, err);
+            return;
+        }
+        if members
+            .iter()
+            .any(|member| *member != uuid && !self.accepts_messages(member, &uuid))
+        {
+            send_error(
+                &addr,
+                user_id,
+                ClientError::PrivateMessageNotAccepted.into(),
+            );
+            return;
+        }
+
+        let (limiter, groups) = (&mut self.group_limiter, &mut self.groups);
+        let result = limiter
+            .check(&uuid)
+            .and_then(|()| groups.create(uuid, name, members));
+        match result {
+            Ok(group) => {
+                info!("User `{}` created group `{}`.", user_id, group.id);
+                let group = group.clone();
+                self.send_group(&group);
+            }
+            Err(err) => send_error(&addr, user_id, err),
+        }
+    }
+
+    pub(super) fn handle_add_group_member(&mut self, user_id: InternalId, id: GroupId, user: Uuid) {
+        let (addr, uuid) = match self.logged_in_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+        if !self.accepts_messages(&user, &uuid) {
+            send_error(
+                &addr,
+                user_id,
+                ClientError::PrivateMessageNotAccepted.into(),
+            );
+            return;
+        }
+
+        match self.groups.add_member(id, &uuid, user) {
+            Ok(group) => {
+                info!("User `{}` added `{}` to group `{}`.", user_id, user, id);
+                let group = group.clone();
+                self.send_group(&group);
+            }
+            Err(err) => send_error(&addr, user_id, err),
+        }
+    }
+
+    pub(super) fn handle_remove_group_member(
+        &mut self,
+        user_id: InternalId,
+        id: GroupId,
+        user: Option<Uuid>,
+    ) {
+        let (addr, uuid) = match self.logged_in_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+        // without a user, the sender leaves the group
+        let user = user.unwrap_or(uuid);
+
+        match self.groups.remove_member(id, &uuid, &user) {
+            Ok(()) => {
+                info!("User `{}` removed `{}` from group `{}`.", user_id, user, id);
+                self.send_to_user(&user, ClientPacket::GroupRemoved { group: id });
+                if let Ok(group) = self.groups.get_any(id) {
+                    let group = group.clone();
+                    self.send_group(&group);
+                }
+            }
+            Err(err) => send_error(&addr, user_id, err),
+        }
+    }
+
+    pub(super) fn handle_list_groups(&mut self, user_id: InternalId) {
+        let session = match self.logged_in_session(user_id) {
+            Some(session) => session,
+            None => return,
+        };
+        let uuid = session.user.as_ref().unwrap().uuid;
+
+        let groups = self.groups.of_user(&uuid).cloned().collect();
+        if let Err(err) = session.addr.do_send(ClientPacket::Groups { groups }) {
+            warn!("Could not send groups to user `{}`: {}", user_id, err);
+        }
+    }
+
+    /// Sends the current state of a group to all of its members.
+    fn send_group(&self, group: &Group) {
+        for member in &group.members {
+            let client_packet = ClientPacket::GroupChanged {
+                group: group.clone(),
+            };
+            self.send_to_user(member, client_packet);
+        }
+    }
+}
+
+fn send_error(addr: &Recipient<ClientPacket>, user_id: InternalId, err: Error) {
+    match err {
+        Error::AxoChat { source } => {
+            info!("Group action of `{}` failed: {}", user_id, source);
+            addr.do_send(ClientPacket::Error { message: source }).ok();
+        }
+        err => {
+            warn!("Group action of `{}` failed: {}", user_id, err);
+            addr.do_send(ClientPacket::Error {
+                message: ClientError::Internal,
+            })
+            .ok();
+        }
+    }
+}
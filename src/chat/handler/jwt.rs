@@ -2,10 +2,11 @@ use crate::error::*;
 use log::*;
 
 use super::{ChatServer, ClientPacket};
+use crate::audit::AuditAction;
 use crate::auth::UserInfo;
-use crate::chat::{InternalId, SuccessReason, User, UserSession};
-use crate::message::RateLimiter;
-use std::collections::HashSet;
+use crate::chat::{InternalId, SuccessReason};
+use crate::provider::AuthMethod;
+use uuid::Uuid;
 
 impl ChatServer {
     pub(super) fn handle_request_jwt(&mut self, user_id: InternalId) {
@@ -14,12 +15,26 @@ impl ChatServer {
             .get(&user_id)
             .expect("could not find connection");
         if let Some(auth) = &self.authenticator {
-            if let Some(user) = &session.user {
-                let token = match auth.new_token(UserInfo {
+            // anonymous identities only last for a single connection
+            if session.auth_method == Some(AuthMethod::Anonymous) {
+                info!("Anonymous user `{}` tried to get JWT.", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotPermitted,
+                    })
+                    .ok();
+            } else if let Some(user) = &session.user {
+                let info = UserInfo {
                     name: user.name.clone(),
                     uuid: user.uuid,
-                }) {
-                    Ok(token) => token,
+                };
+                let role = self.moderation.role(&user.uuid);
+                let tokens = auth
+                    .new_token(info.clone(), role)
+                    .and_then(|token| Ok((token, auth.new_refresh_token(info, role)?)));
+                let (token, refresh_token) = match tokens {
+                    Ok(tokens) => tokens,
                     Err(err) => {
                         warn!("Could not create new token for user `{}`: {}", user_id, err);
                         session
@@ -32,7 +47,10 @@ impl ChatServer {
                     }
                 };
 
-                if let Err(err) = session.addr.do_send(ClientPacket::NewJWT { token }) {
+                if let Err(err) = session.addr.do_send(ClientPacket::NewJWT {
+                    token,
+                    refresh_token,
+                }) {
                     warn!("Could not send mojang info to user `{}`: {}", user_id, err);
                 }
             } else {
@@ -55,57 +73,122 @@ impl ChatServer {
         }
     }
 
-    pub(super) fn handle_login_jwt(
-        &mut self,
-        user_id: InternalId,
-        jwt: &str,
-        allow_messages: bool,
-    ) {
+    pub(super) fn handle_refresh_jwt(&mut self, user_id: InternalId, refresh_token: &str) {
         let session = self
             .connections
-            .get_mut(&user_id)
+            .get(&user_id)
             .expect("could not find connection");
-        if let Some(auth) = &self.authenticator {
-            match auth.auth(jwt) {
-                Ok(info) => {
-                    self.users
-                        .entry(info.name.clone())
-                        .or_insert(UserSession {
-                            rate_limiter: RateLimiter::new(self.config.message.clone()),
-                            connections: HashSet::new(),
-                        })
-                        .connections
-                        .insert(user_id);
+        let auth = match &self.authenticator {
+            Some(auth) => auth,
+            None => {
+                info!("User `{}` tried to refresh not supported JWT", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotSupported,
+                    })
+                    .ok();
+                return;
+            }
+        };
 
-                    session.user = Some(User {
-                        name: info.name,
-                        uuid: info.uuid,
-                        allow_messages,
-                    });
-                    if let Err(err) = session.addr.do_send(ClientPacket::Success {
-                        reason: SuccessReason::Login,
-                    }) {
-                        info!("Could not send login success to `{}`: {}", user_id, err);
-                    }
-                }
-                Err(err) => {
-                    info!("Login of user `{}` using JWT failed: {}", user_id, err);
-                    session
-                        .addr
-                        .do_send(ClientPacket::Error {
-                            message: ClientError::LoginFailed,
-                        })
-                        .ok();
+        match auth.refresh(refresh_token, &self.moderation) {
+            Ok((info, token, refresh_token)) => {
+                info!("User `{}` refreshed the JWT of `{}`.", user_id, info.uuid);
+                if let Err(err) = session.addr.do_send(ClientPacket::NewJWT {
+                    token,
+                    refresh_token,
+                }) {
+                    warn!("Could not send JWT to user `{}`: {}", user_id, err);
                 }
-            };
-        } else {
-            info!("User `{}` tried to request not supported JWT", user_id);
+            }
+            Err(err) => {
+                info!("User `{}` could not refresh JWT: {}", user_id, err);
+                let message = match err {
+                    Error::AxoChat { source } => source,
+                    Error::JWT { .. } => ClientError::LoginFailed,
+                    _ => ClientError::Internal,
+                };
+                session.addr.do_send(ClientPacket::Error { message }).ok();
+            }
+        }
+    }
+
+    /// Revokes all tokens of `user`, or of the sender if `user` is `None`.
+    /// Revoking the tokens of other users requires a higher role than theirs.
+    pub(super) fn handle_revoke_tokens(&mut self, user_id: InternalId, user: Option<Uuid>) {
+        let own = match self.logged_in_session(user_id) {
+            Some(session) => session.user.as_ref().unwrap().uuid,
+            None => return,
+        };
+        let target = user.unwrap_or(own);
+        if target != own {
+            if self.moderator_session(user_id).is_none() {
+                return;
+            }
+            if self.moderation.role(&target) >= self.moderation.role(&own) {
+                info!(
+                    "User `{}` tried to revoke the tokens of `{}` without permission.",
+                    user_id, target
+                );
+                self.connections[&user_id]
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotPermitted,
+                    })
+                    .ok();
+                return;
+            }
+        }
+
+        let session = &self.connections[&user_id];
+        let result = match &self.authenticator {
+            Some(auth) => auth.revoke(target),
+            None => {
+                info!("User `{}` tried to revoke not supported JWT", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotSupported,
+                    })
+                    .ok();
+                return;
+            }
+        };
+        if let Err(err) = result {
+            warn!("Could not revoke tokens of `{}`: {}", target, err);
             session
                 .addr
                 .do_send(ClientPacket::Error {
-                    message: ClientError::NotSupported,
+                    message: ClientError::Internal,
                 })
                 .ok();
+            return;
+        }
+
+        info!("User `{}` revoked the tokens of `{}`.", user_id, target);
+        // sessions which were logged in with the revoked tokens must not stay logged in,
+        // only the connection of the sender is kept
+        let sessions: Vec<InternalId> = self
+            .connection_details(&target)
+            .iter()
+            .filter(|connection| connection.auth_method == Some(AuthMethod::JWT))
+            .map(|connection| connection.id)
+            .filter(|id| *id != user_id)
+            .collect();
+        for id in sessions {
+            self.terminate_session(id, ClientError::SessionTerminated);
+        }
+        if target != own {
+            self.audit(own, AuditAction::RevokeTokens { user: target }, None);
+        }
+        if let Err(err) = self.connections[&user_id]
+            .addr
+            .do_send(ClientPacket::Success {
+                reason: SuccessReason::RevokeTokens,
+            })
+        {
+            warn!("Could not send success to user `{}`: {}", user_id, err);
         }
     }
 }
@@ -0,0 +1,159 @@
+use crate::error::*;
+use log::*;
+
+use crate::auth::UserInfo;
+use crate::chat::{ChatServer, ClientPacket, InternalId, SuccessReason, User};
+use crate::provider::{AuthMethod, Credentials};
+
+use actix::*;
+
+impl ChatServer {
+    /// Verifies `credentials` with the provider of their method and logs the user in.
+    pub(super) fn login(
+        &mut self,
+        user_id: InternalId,
+        credentials: Credentials,
+        allow_messages: bool,
+        identifier: Option<String>,
+        ctx: &mut Context<Self>,
+    ) {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        if session.is_logged_in() {
+            info!("User `{}` tried to log in multiple times.", user_id);
+            session
+                .addr
+                .do_send(ClientPacket::Error {
+                    message: ClientError::AlreadyLoggedIn,
+                })
+                .ok();
+            return;
+        }
+
+        let method = credentials.method();
+        let provider = match self.providers.get(&method) {
+            Some(provider) => provider,
+            None => {
+                info!("User `{}` tried to log in using {:?}.", user_id, method);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotSupported,
+                    })
+                    .ok();
+                return;
+            }
+        };
+
+        provider
+            .authenticate(credentials)
+            .into_actor(self)
+            .then(move |res, actor, _ctx| {
+                match res {
+                    Ok(identity) => {
+                        actor.complete_login(user_id, method, identity, allow_messages, identifier)
+                    }
+                    Err(err) => {
+                        if let Some(session) = actor.connections.get(&user_id) {
+                            info!(
+                                "Login of user `{}` using {:?} failed: {}",
+                                user_id, method, err
+                            );
+                            let message = match err {
+                                Error::AxoChat { source } => source,
+                                _ => ClientError::LoginFailed,
+                            };
+                            session.addr.do_send(ClientPacket::Error { message }).ok();
+                        }
+                    }
+                }
+                fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Logs in a user whose identity was verified.
+    /// This is shared by all authentication methods.
+    fn complete_login(
+        &mut self,
+        user_id: InternalId,
+        method: AuthMethod,
+        identity: UserInfo,
+        allow_messages: bool,
+        identifier: Option<String>,
+    ) {
+        let session = match self.connections.get(&user_id) {
+            Some(session) => session,
+            // the connection was closed while it was verified
+            None => return,
+        };
+        if session.is_logged_in() {
+            // another login of the same connection completed first
+            info!("User `{}` tried to log in multiple times.", user_id);
+            session
+                .addr
+                .do_send(ClientPacket::Error {
+                    message: ClientError::AlreadyLoggedIn,
+                })
+                .ok();
+            return;
+        }
+        info!(
+            "User `{}` has uuid `{}` and username `{}`",
+            user_id, identity.uuid, identity.name
+        );
+
+        if self
+            .moderation
+            .check_target_ban(session.ip, identifier.as_deref(), &identity.uuid)
+        {
+            info!("User `{}` tried to log in while banned.", user_id);
+            session
+                .addr
+                .do_send(ClientPacket::Error {
+                    message: ClientError::Banned,
+                })
+                .ok();
+            return;
+        }
+        if !self.reserve_connection(&identity.uuid) {
+            info!("User `{}` has too many connections.", user_id);
+            self.connections[&user_id]
+                .addr
+                .do_send(ClientPacket::Error {
+                    message: ClientError::TooManyConnections,
+                })
+                .ok();
+            return;
+        }
+
+        // anonymous identities are only valid for a single connection
+        if method != AuthMethod::Anonymous {
+            if let Err(err) = self.directory.see(&identity.uuid, &identity.name) {
+                warn!("Could not register user `{}`: {}", identity.uuid, err);
+            }
+        }
+
+        self.users.add(user_id, identity.uuid, &identity.name);
+
+        let session = self.connections.get_mut(&user_id).unwrap();
+        session.user = Some(User {
+            name: identity.name,
+            uuid: identity.uuid,
+            allow_messages,
+            identifier,
+        });
+        session.auth_method = Some(method);
+        if let Err(err) = session.addr.do_send(ClientPacket::Success {
+            reason: SuccessReason::Login,
+        }) {
+            info!("Could not send login success to `{}`: {}", user_id, err);
+        }
+        self.send_resume_token(user_id);
+        self.send_chat_mode(user_id);
+        self.send_preferences(user_id);
+        self.notify_login(user_id);
+    }
+}
@@ -0,0 +1,66 @@
+use super::{ChatServer, ClientPacket};
+use crate::chat::{InternalId, UserDetails};
+use crate::time::unix_secs;
+
+use crate::error::*;
+use log::*;
+use uuid::Uuid;
+
+/// The maximum amount of recent messages included in a lookup.
+const LOOKUP_MESSAGES: usize = 20;
+
+impl ChatServer {
+    pub(super) fn handle_lookup_user(
+        &mut self,
+        user_id: InternalId,
+        uuid: Option<Uuid>,
+        name: Option<String>,
+    ) {
+        let session = match self.moderator_session(user_id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        let uuid = uuid.or_else(|| self.resolve_user(&name?));
+        let uuid = match uuid {
+            Some(uuid) => uuid,
+            None => {
+                info!("User `{}` tried to look up an unknown user.", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::UnknownUser,
+                    })
+                    .ok();
+                return;
+            }
+        };
+
+        let details = UserDetails {
+            uuid,
+            names: self.directory.names(&uuid).to_vec(),
+            first_seen: self.directory.first_seen(&uuid).map(unix_secs),
+            connections: self.connection_details(&uuid),
+            moderator: self.moderation.is_moderator(&uuid),
+            banned: self.moderation.is_banned(&uuid),
+            shadow_banned: self.moderation.is_shadow_banned(&uuid),
+            reports: self.reports.of_user(&uuid).count(),
+            open_reports: self
+                .reports
+                .of_user(&uuid)
+                .filter(|report| report.resolved_by.is_none())
+                .count(),
+            messages: self
+                .history
+                .by_author(&uuid)
+                .take(LOOKUP_MESSAGES)
+                .cloned()
+                .collect(),
+        };
+
+        info!("User `{}` looked up `{}`.", user_id, uuid);
+        if let Err(err) = session.addr.do_send(ClientPacket::UserDetails(details)) {
+            warn!("Could not send user details to `{}`: {}", user_id, err);
+        }
+    }
+}
@@ -1,37 +1,69 @@
 use super::{ChatServer, ClientPacket};
 use crate::auth::UserInfo;
-use crate::chat::{InternalId, SessionState};
+use crate::chat::{GroupId, InternalId, SessionState};
+use crate::message::{Channel, StoredMessage};
 
 use crate::error::*;
 use log::*;
+use std::time::Duration;
+use uuid::Uuid;
 
 impl ChatServer {
     pub(super) fn handle_message(&mut self, user_id: InternalId, content: String) {
-        if self.check_ratelimit(user_id, content.clone()) {
+        if self.check_ratelimit(user_id, Channel::Public) {
             return;
         }
 
         if self.basic_check(user_id, &content).is_some() {
+            if self.check_spam(user_id, &content) {
+                return;
+            }
+            let content = match self.filter_message(user_id, &content) {
+                Some(content) => content,
+                None => return,
+            };
+            let id = self.next_message_id();
+
             let session = self
                 .connections
-                .get_mut(&user_id)
+                .get(&user_id)
                 .expect("could not find connection");
 
             let info = session.user.as_ref().unwrap();
+            let author_info = UserInfo {
+                name: info.name.clone(),
+                uuid: info.uuid,
+            };
+            self.history.push(StoredMessage {
+                id,
+                author: author_info.clone(),
+                receiver: None,
+                group: None,
+                content: content.clone(),
+            });
 
-            info!("User `{}` has written `{}`.", user_id, content);
+            let author = author_info.uuid;
+            if self.moderation.is_shadow_banned(&author) {
+                info!(
+                    "Shadow banned user `{}` has written `{}`.",
+                    user_id, content
+                );
+            } else {
+                info!("User `{}` has written `{}`.", user_id, content);
+            }
             let client_packet = ClientPacket::Message {
-                author_info: UserInfo {
-                    name: info.name.clone(),
-                    uuid: info.uuid,
-                },
+                id,
+                author_info: author_info.clone(),
+                content: content.clone(),
+                shadow_banned: false,
+            };
+            let moderator_packet = ClientPacket::Message {
+                id,
+                author_info,
                 content,
+                shadow_banned: true,
             };
-            for session in self.connections.values() {
-                if let Err(err) = session.addr.do_send(client_packet.clone()) {
-                    warn!("Could not send message to client: {}", err);
-                }
-            }
+            self.deliver(&author, None, client_packet, moderator_packet);
         }
     }
 
@@ -41,61 +73,228 @@ impl ChatServer {
         receiver: String,
         content: String,
     ) {
-        if self.check_ratelimit(user_id, content.clone()) {
+        if self.check_ratelimit(user_id, Channel::Private) {
             return;
         }
 
-        if let Some(sender_session) = self.basic_check(user_id, &content) {
-            let sender_info = sender_session.user.as_ref().unwrap();
+        if self.basic_check(user_id, &content).is_none() || self.check_spam(user_id, &content) {
+            return;
+        }
+        let content = match self.filter_message(user_id, &content) {
+            Some(content) => content,
+            None => return,
+        };
+        let id = self.next_message_id();
 
-            let receiver_user = match self.users.get(&receiver) {
-                Some(user) => user,
-                None => {
-                    debug!(
-                        "User `{}` tried to write to non-existing user `{}`.",
-                        user_id, receiver
-                    );
-                    return;
+        let sender_session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        let sender_info = sender_session.user.as_ref().unwrap();
+        let receiver_uuid = match self
+            .users
+            .find(&receiver)
+            .or_else(|| self.suspended.find(&receiver))
+        {
+            Some(uuid) => uuid,
+            None => {
+                debug!(
+                    "User `{}` tried to write to non-existing user `{}`.",
+                    user_id, receiver
+                );
+                return;
+            }
+        };
+        if !self.accepts_messages(&receiver_uuid, &sender_info.uuid) {
+            info!(
+                "User `{}` tried to write to `{}`, who does not accept the message.",
+                user_id, receiver
+            );
+            sender_session
+                .addr
+                .do_send(ClientPacket::Error {
+                    message: ClientError::PrivateMessageNotAccepted,
+                })
+                .ok();
+            return;
+        }
+
+        let author_info = UserInfo {
+            name: sender_info.name.clone(),
+            uuid: sender_info.uuid,
+        };
+        let client_packet = ClientPacket::PrivateMessage {
+            id,
+            author_info: author_info.clone(),
+            content: content.clone(),
+            shadow_banned: false,
+        };
+        let moderator_packet = ClientPacket::PrivateMessage {
+            id,
+            author_info: author_info.clone(),
+            content: content.clone(),
+            shadow_banned: true,
+        };
+        if self.moderation.is_shadow_banned(&author_info.uuid) {
+            info!(
+                "Shadow banned user `{}` has written to `{}` privately.",
+                user_id, receiver
+            );
+        } else {
+            info!(
+                "User `{}` has written to `{}` privately.",
+                user_id, receiver
+            );
+        }
+        self.deliver(
+            &author_info.uuid,
+            Some(&[receiver_uuid]),
+            client_packet,
+            moderator_packet,
+        );
+        self.history.push(StoredMessage {
+            id,
+            author: author_info,
+            receiver: Some(receiver_uuid),
+            group: None,
+            content,
+        });
+    }
+
+    pub(super) fn handle_group_message(
+        &mut self,
+        user_id: InternalId,
+        group: GroupId,
+        content: String,
+    ) {
+        if self.check_ratelimit(user_id, Channel::Private) {
+            return;
+        }
+
+        if self.basic_check(user_id, &content).is_none() || self.check_spam(user_id, &content) {
+            return;
+        }
+        let content = match self.filter_message(user_id, &content) {
+            Some(content) => content,
+            None => return,
+        };
+
+        let sender_session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        let sender_info = sender_session.user.as_ref().unwrap();
+        let members: Vec<Uuid> = match self.groups.get(group, &sender_info.uuid) {
+            Ok(group) => group.members.iter().cloned().collect(),
+            Err(err) => {
+                info!(
+                    "User `{}` tried to write to unknown group `{}`.",
+                    user_id, group
+                );
+                if let Error::AxoChat { source } = err {
+                    sender_session
+                        .addr
+                        .do_send(ClientPacket::Error { message: source })
+                        .ok();
                 }
-            };
+                return;
+            }
+        };
 
-            for receiver_session in receiver_user
-                .connections
-                .iter()
-                .filter_map(|id| self.connections.get(id))
-            {
-                match &receiver_session.user {
-                    Some(info) if info.allow_messages => {
-                        let client_packet = ClientPacket::PrivateMessage {
-                            author_info: UserInfo {
-                                name: sender_info.name.clone(),
-                                uuid: sender_info.uuid,
-                            },
-                            content: content.clone(),
-                        };
-                        info!(
-                            "User `{}` has written to `{}` privately.",
-                            user_id, receiver
-                        );
-                        if let Err(err) = receiver_session.addr.do_send(client_packet) {
-                            warn!("Could not send private message to client: {}", err);
-                        } else {
-                            return;
-                        }
-                    }
-                    _ => {}
+        let author_info = UserInfo {
+            name: sender_info.name.clone(),
+            uuid: sender_info.uuid,
+        };
+        let id = self.next_message_id();
+        let client_packet = ClientPacket::GroupMessage {
+            id,
+            group,
+            author_info: author_info.clone(),
+            content: content.clone(),
+            shadow_banned: false,
+        };
+        let moderator_packet = ClientPacket::GroupMessage {
+            id,
+            group,
+            author_info: author_info.clone(),
+            content: content.clone(),
+            shadow_banned: true,
+        };
+        if self.moderation.is_shadow_banned(&author_info.uuid) {
+            info!(
+                "Shadow banned user `{}` has written to group `{}`.",
+                user_id, group
+            );
+        } else {
+            info!("User `{}` has written to group `{}`.", user_id, group);
+        }
+        self.deliver(
+            &author_info.uuid,
+            Some(&members),
+            client_packet,
+            moderator_packet,
+        );
+        self.history.push(StoredMessage {
+            id,
+            author: author_info,
+            receiver: None,
+            group: Some(group),
+            content,
+        });
+    }
+
+    /// Sends a message to its `receivers`, or to every connection if there are none.
+    ///
+    /// Messages of shadow banned users are only sent to the author, if they are a receiver,
+    /// and to moderators, who receive `moderator_packet` instead.
+    /// This way the author does not notice the ban.
+    fn deliver(
+        &mut self,
+        author: &Uuid,
+        receivers: Option<&[Uuid]>,
+        client_packet: ClientPacket,
+        moderator_packet: ClientPacket,
+    ) {
+        let shadow_banned = self.moderation.is_shadow_banned(author);
+        if !shadow_banned {
+            if let Some(receivers) = receivers {
+                for receiver in receivers {
+                    self.send_to_user(receiver, client_packet.clone());
+                    self.suspended.send_to_user(receiver, &client_packet);
                 }
+                return;
             }
         }
 
-        let _ = self
-            .connections
-            .get_mut(&user_id)
-            .expect("could not find connection")
-            .addr
-            .do_send(ClientPacket::Error {
-                message: ClientError::PrivateMessageNotAccepted,
-            });
+        let moderation = &self.moderation;
+        let packet_for = |user: Option<&Uuid>| {
+            let receives = match (receivers, user) {
+                (None, _) => true,
+                (Some(receivers), Some(user)) => receivers.contains(user),
+                (Some(_), None) => false,
+            };
+            if shadow_banned && user != Some(author) {
+                // moderators see the message even if they are no receivers
+                if user.is_some_and(|user| moderation.is_moderator(user)) {
+                    Some(&moderator_packet)
+                } else {
+                    None
+                }
+            } else if receives {
+                Some(&client_packet)
+            } else {
+                None
+            }
+        };
+        for session in self.connections.values() {
+            if let Some(packet) = packet_for(session.user.as_ref().map(|user| &user.uuid)) {
+                if let Err(err) = session.addr.do_send(packet.clone()) {
+                    warn!("Could not send message to client: {}", err);
+                }
+            }
+        }
+        self.suspended
+            .broadcast(|user| packet_for(Some(&user.uuid)));
     }
 
     fn basic_check(&self, user_id: InternalId, content: &str) -> Option<&SessionState> {
@@ -116,7 +315,11 @@ impl ChatServer {
 
                 return None;
             }
-            if self.moderation.is_banned(&info.uuid) {
+            let target_banned = !self.moderation.is_moderator(&info.uuid)
+                && self
+                    .moderation
+                    .is_target_banned(session.ip, info.identifier.as_deref());
+            if self.moderation.is_banned(&info.uuid) || target_banned {
                 info!("User `{}` tried to send message while banned", user_id);
                 session
                     .addr
@@ -127,6 +330,19 @@ impl ChatServer {
 
                 return None;
             }
+            if !self.moderation.is_moderator(&info.uuid) {
+                if let Err(err) = self.links.check(content) {
+                    info!("User `{}` tried to send a link: {}", user_id, err);
+                    if let Error::AxoChat { source } = err {
+                        session
+                            .addr
+                            .do_send(ClientPacket::Error { message: source })
+                            .ok();
+                    }
+
+                    return None;
+                }
+            }
 
             Some(session)
         } else {
@@ -141,31 +357,154 @@ impl ChatServer {
         }
     }
 
-    fn check_ratelimit(&mut self, user_id: InternalId, message: String) -> bool {
+    /// Applies the filter to the message of a logged in user.
+    /// Moderators are notified of flagged messages.
+    /// Returns the censored message or `None` if it was blocked.
+    fn filter_message(&self, user_id: InternalId, content: &str) -> Option<String> {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        let info = session.user.as_ref().unwrap();
+
+        match self.filter.apply(content) {
+            Ok(filtered) => {
+                if !filtered.flagged.is_empty() {
+                    info!(
+                        "Message of user `{}` was flagged: {:?}",
+                        user_id, filtered.flagged
+                    );
+                    let client_packet = ClientPacket::MessageFlagged {
+                        author_info: UserInfo {
+                            name: info.name.clone(),
+                            uuid: info.uuid,
+                        },
+                        content: content.to_owned(),
+                        flagged: filtered.flagged,
+                    };
+                    for session in self.connections.values() {
+                        match &session.user {
+                            Some(user) if self.moderation.is_moderator(&user.uuid) => {
+                                if let Err(err) = session.addr.do_send(client_packet.clone()) {
+                                    warn!("Could not send flagged message to moderator: {}", err);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+
+                Some(filtered.content)
+            }
+            Err(err) => {
+                info!("Message of user `{}` was blocked: {}", user_id, err);
+                if let Error::AxoChat { source } = err {
+                    session
+                        .addr
+                        .do_send(ClientPacket::Error { message: source })
+                        .ok();
+                }
+                None
+            }
+        }
+    }
+
+    /// Checks if the message is rate limited or not allowed by the current chat mode.
+    fn check_ratelimit(&mut self, user_id: InternalId, channel: Channel) -> bool {
         let session = self
             .connections
             .get(&user_id)
             .expect("could not find connection");
 
         if let Some(user) = &session.user {
-            let user = self.users.get_mut(&user.name).unwrap();
-            if user.rate_limiter.check_new_message(message) {
-                info!(
-                    "User `{}` tried to send message, but was rate limited.",
-                    user_id
-                );
-                session
-                    .addr
-                    .do_send(ClientPacket::Error {
-                        message: ClientError::RateLimited,
-                    })
-                    .ok();
-                true
+            let restricted = match channel {
+                Channel::Public => !self.moderation.is_moderator(&user.uuid),
+                Channel::Private => false,
+            };
+            let limits = self.rate_limits(&user.uuid);
+            let mut res = if restricted {
+                self.check_chat_mode(&user.uuid)
             } else {
-                false
+                Ok(())
+            };
+            if res.is_ok() {
+                let user_session = self.users.get_mut(&user.uuid).unwrap();
+                res = match self.mode.slow_mode {
+                    Some(secs) if restricted => user_session
+                        .rate_limiter
+                        .check_slow_mode(Duration::from_secs(secs)),
+                    _ => user_session
+                        .rate_limiter
+                        .check_new_message(channel, &limits),
+                };
+            }
+
+            match res {
+                Ok(()) => false,
+                Err(err) => {
+                    info!(
+                        "User `{}` tried to send message, but was rejected: {}",
+                        user_id, err
+                    );
+                    if let Error::AxoChat { source } = err {
+                        session
+                            .addr
+                            .do_send(ClientPacket::Error { message: source })
+                            .ok();
+                    }
+                    true
+                }
             }
         } else {
             false
         }
     }
+
+    /// Checks if a valid message of a logged in user is spam.
+    /// Messages which are blocked by the filter afterwards are remembered as well,
+    /// so that repeating them is also rejected as spam.
+    fn check_spam(&mut self, user_id: InternalId, message: &str) -> bool {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        let uuid = session.user.as_ref().unwrap().uuid;
+
+        match self.spam.check_new_message(&uuid, message) {
+            Ok(()) => false,
+            Err(err) => {
+                info!(
+                    "User `{}` tried to send message, but was rejected: {}",
+                    user_id, err
+                );
+                if let Error::AxoChat { source } = err {
+                    session
+                        .addr
+                        .do_send(ClientPacket::Error { message: source })
+                        .ok();
+                }
+                true
+            }
+        }
+    }
+
+    /// Checks whether a user who is not a moderator may write in public chat
+    /// with the current chat mode.
+    fn check_chat_mode(&self, uuid: &Uuid) -> Result<()> {
+        if self.mode.lockdown {
+            return Err(ClientError::Lockdown.into());
+        }
+
+        if let Some(seen_before) = self.mode.seen_before_time() {
+            let too_new = self
+                .directory
+                .first_seen(uuid)
+                .is_none_or(|first_seen| first_seen >= seen_before);
+            if too_new {
+                return Err(ClientError::AccountTooNew.into());
+            }
+        }
+
+        Ok(())
+    }
 }
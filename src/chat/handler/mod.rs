@@ -1,12 +1,28 @@
+mod audit;
 mod ban;
 mod count;
+mod friends;
+mod group;
 mod jwt;
+mod login;
+mod lookup;
 mod message;
+mod mode;
 mod mojang;
+mod preferences;
+mod report;
+mod resume;
+mod sessions;
 
-use super::{ChatServer, ClientPacket, ServerPacket, ServerPacketId};
+use super::{ChatServer, ClientPacket, InternalId, ServerPacket, ServerPacketId, SessionState};
+
+use crate::error::*;
+use crate::moderation::Moderation;
+use crate::provider::Credentials;
+use log::*;
 
 use actix::*;
+use uuid::Uuid;
 
 impl Handler<ServerPacketId> for ChatServer {
     type Result = ();
@@ -16,6 +32,26 @@ impl Handler<ServerPacketId> for ChatServer {
         ServerPacketId { user_id, packet }: ServerPacketId,
         ctx: &mut Context<Self>,
     ) {
+        let session = match self.connections.get_mut(&user_id) {
+            Some(session) => session,
+            None => {
+                debug!("Received packet from unknown connection `{}`.", user_id);
+                return;
+            }
+        };
+        if !session.is_logged_in() {
+            if let Err(retry_after) = session.packet_limiter.check_new_packet() {
+                info!("Connection `{}` sent too many packets.", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::rate_limited(retry_after),
+                    })
+                    .ok();
+                return;
+            }
+        }
+
         match packet {
             ServerPacket::RequestMojangInfo => {
                 self.handle_request_mojang_info(user_id);
@@ -26,25 +62,190 @@ impl Handler<ServerPacketId> for ChatServer {
             ServerPacket::RequestJWT => {
                 self.handle_request_jwt(user_id);
             }
+            ServerPacket::RefreshJWT { refresh_token } => {
+                self.handle_refresh_jwt(user_id, &refresh_token);
+            }
+            ServerPacket::RevokeTokens { user } => {
+                self.handle_revoke_tokens(user_id, user);
+            }
             ServerPacket::LoginJWT {
                 token,
                 allow_messages,
+                identifier,
+            } => {
+                let credentials = Credentials::JWT { token };
+                self.login(user_id, credentials, allow_messages, identifier, ctx);
+            }
+            ServerPacket::LoginMinecraft {
+                token,
+                allow_messages,
+                identifier,
+            } => {
+                let credentials = Credentials::MinecraftServices { token };
+                self.login(user_id, credentials, allow_messages, identifier, ctx);
+            }
+            ServerPacket::LoginAnonymous {
+                allow_messages,
+                identifier,
             } => {
-                self.handle_login_jwt(user_id, &token, allow_messages);
+                self.login(
+                    user_id,
+                    Credentials::Anonymous,
+                    allow_messages,
+                    identifier,
+                    ctx,
+                );
+            }
+            ServerPacket::Resume { token } => {
+                self.handle_resume(user_id, &token);
             }
             ServerPacket::Message { content } => self.handle_message(user_id, content),
             ServerPacket::PrivateMessage { receiver, content } => {
                 self.handle_private_message(user_id, receiver, content);
             }
-            ServerPacket::BanUser { user } => {
-                self.ban_user(user_id, &user);
+            ServerPacket::BanUser {
+                user,
+                shadow,
+                reason,
+            } => {
+                self.ban_user(user_id, &user, shadow, reason);
+            }
+            ServerPacket::UnbanUser { user, reason } => {
+                self.unban_user(user_id, &user, reason);
+            }
+            ServerPacket::BanTarget { target, reason } => {
+                self.ban_target(user_id, target, reason);
+            }
+            ServerPacket::UnbanTarget { target, reason } => {
+                self.unban_target(user_id, target, reason);
+            }
+            ServerPacket::ListTargetBans => {
+                self.handle_list_target_bans(user_id);
+            }
+            ServerPacket::SetPreferences(preferences) => {
+                self.handle_set_preferences(user_id, preferences);
+            }
+            ServerPacket::SendFriendRequest { user } => {
+                self.handle_send_friend_request(user_id, &user);
+            }
+            ServerPacket::AcceptFriendRequest { user } => {
+                self.handle_accept_friend_request(user_id, user);
+            }
+            ServerPacket::RemoveFriend { user } => {
+                self.handle_remove_friend(user_id, user);
+            }
+            ServerPacket::ListFriends => {
+                self.handle_list_friends(user_id);
+            }
+            ServerPacket::CreateGroup { name, members } => {
+                self.handle_create_group(user_id, name, members);
+            }
+            ServerPacket::GroupMessage { group, content } => {
+                self.handle_group_message(user_id, group, content);
             }
-            ServerPacket::UnbanUser { user } => {
-                self.unban_user(user_id, &user);
+            ServerPacket::AddGroupMember { group, user } => {
+                self.handle_add_group_member(user_id, group, user);
+            }
+            ServerPacket::RemoveGroupMember { group, user } => {
+                self.handle_remove_group_member(user_id, group, Some(user));
+            }
+            ServerPacket::LeaveGroup { group } => {
+                self.handle_remove_group_member(user_id, group, None);
+            }
+            ServerPacket::ListGroups => {
+                self.handle_list_groups(user_id);
+            }
+            ServerPacket::LookupUser { uuid, name } => {
+                self.handle_lookup_user(user_id, uuid, name);
             }
             ServerPacket::RequestUserCount => {
                 self.send_user_count(user_id);
             }
+            ServerPacket::RequestAuthStats => {
+                self.send_auth_stats(user_id);
+            }
+            ServerPacket::ListSessions => {
+                self.handle_list_sessions(user_id);
+            }
+            ServerPacket::TerminateSession { id } => {
+                self.handle_terminate_session(user_id, id);
+            }
+            ServerPacket::ReportMessage { message_id, reason } => {
+                self.handle_report_message(user_id, message_id, reason);
+            }
+            ServerPacket::ReportUser { user, reason } => {
+                self.handle_report_user(user_id, user, reason);
+            }
+            ServerPacket::ListReports => {
+                self.handle_list_reports(user_id);
+            }
+            ServerPacket::ResolveReport { id } => {
+                self.handle_resolve_report(user_id, id);
+            }
+            ServerPacket::RequestAuditLog(filter) => {
+                self.handle_request_audit_log(user_id, &filter);
+            }
+            ServerPacket::SetChatMode(mode) => {
+                self.handle_set_chat_mode(user_id, mode);
+            }
+        }
+    }
+}
+
+impl ChatServer {
+    /// Returns the session if the user is logged in.
+    /// Otherwise an error is sent to the user.
+    fn logged_in_session(&self, user_id: InternalId) -> Option<&SessionState> {
+        self.permitted_session(user_id, |_, _| true)
+    }
+
+    /// Returns the session if the user is a logged in moderator.
+    /// Otherwise an error is sent to the user.
+    fn moderator_session(&self, user_id: InternalId) -> Option<&SessionState> {
+        self.permitted_session(user_id, Moderation::is_moderator)
+    }
+
+    /// Returns the session if the user is a logged in admin.
+    /// Otherwise an error is sent to the user.
+    fn admin_session(&self, user_id: InternalId) -> Option<&SessionState> {
+        self.permitted_session(user_id, Moderation::is_admin)
+    }
+
+    fn permitted_session(
+        &self,
+        user_id: InternalId,
+        permitted: fn(&Moderation, &Uuid) -> bool,
+    ) -> Option<&SessionState> {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+
+        match &session.user {
+            Some(info) if permitted(&self.moderation, &info.uuid) => Some(session),
+            Some(_) => {
+                info!(
+                    "`{}` tried to use a moderator action without permission",
+                    user_id
+                );
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotPermitted,
+                    })
+                    .ok();
+                None
+            }
+            None => {
+                info!("`{}` is not logged in.", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotLoggedIn,
+                    })
+                    .ok();
+                None
+            }
         }
     }
 }
@@ -0,0 +1,53 @@
+use super::{ChatServer, ClientPacket};
+use crate::audit::AuditAction;
+use crate::chat::InternalId;
+use crate::message::ChatMode;
+
+use crate::error::*;
+use log::*;
+
+impl ChatServer {
+    pub(super) fn handle_set_chat_mode(&mut self, user_id: InternalId, mode: ChatMode) {
+        let (addr, actor) = match self.moderator_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+        if let Err(err) = mode.validate() {
+            info!(
+                "User `{}` tried to set an invalid chat mode: {}",
+                user_id, err
+            );
+            if let Error::AxoChat { source } = err {
+                addr.do_send(ClientPacket::Error { message: source }).ok();
+            }
+            return;
+        }
+
+        info!("User `{}` changed the chat mode to {:?}.", user_id, mode);
+        self.mode = mode.clone();
+        self.audit(actor, AuditAction::SetChatMode { mode: mode.clone() }, None);
+
+        let client_packet = ClientPacket::ChatModeChanged { mode };
+        for session in self.connections.values() {
+            if let Err(err) = session.addr.do_send(client_packet.clone()) {
+                warn!("Could not send chat mode to client: {}", err);
+            }
+        }
+    }
+
+    /// Sends the current chat mode to a user who just logged in, unless there are no restrictions.
+    pub(super) fn send_chat_mode(&self, user_id: InternalId) {
+        if self.mode == ChatMode::default() {
+            return;
+        }
+
+        if let Some(session) = self.connections.get(&user_id) {
+            let client_packet = ClientPacket::ChatModeChanged {
+                mode: self.mode.clone(),
+            };
+            if let Err(err) = session.addr.do_send(client_packet) {
+                warn!("Could not send chat mode to user `{}`: {}", user_id, err);
+            }
+        }
+    }
+}
@@ -1,15 +1,10 @@
-use crate::error::*;
 use log::*;
 
-use crate::chat::{ChatServer, ClientPacket, InternalId, SuccessReason, User, UserSession};
-use crate::message::RateLimiter;
-use std::collections::HashSet;
+use crate::chat::{ChatServer, ClientPacket, InternalId, User};
+use crate::provider::Credentials;
 
-use crate::auth::authenticate;
 use actix::*;
 use rand::RngCore;
-use std::str::FromStr;
-use uuid::Uuid;
 
 impl ChatServer {
     pub(super) fn handle_request_mojang_info(&mut self, user_id: InternalId) {
@@ -40,110 +35,21 @@ impl ChatServer {
         info: User,
         ctx: &mut Context<Self>,
     ) {
-        fn send_login_failed(
-            user_id: InternalId,
-            err: Error,
-            session: &Recipient<ClientPacket>,
-            _ctx: &mut Context<ChatServer>,
-        ) {
-            warn!("Could not authenticate user `{}`: {}", user_id, err);
-            session
-                .do_send(ClientPacket::Error {
-                    message: ClientError::LoginFailed,
-                })
-                .ok();
-        }
-
         let session = self
             .connections
             .get(&user_id)
             .expect("could not find connection");
-
-        if session.is_logged_in() {
-            info!("User `{}` tried to log in multiple times.", user_id);
-            session
-                .addr
-                .do_send(ClientPacket::Error {
-                    message: ClientError::AlreadyLoggedIn,
-                })
-                .ok();
-            return;
-        }
-
-        if let Some(session_hash) = &session.session_hash {
-            match authenticate(&info.name, session_hash) {
-                Ok(fut) => {
-                    fut.into_actor(self)
-                        .then(move |res, actor, ctx| {
-                            match res {
-                                Ok(ref mojang_info)
-                                    if Uuid::from_str(&mojang_info.id)
-                                        .expect("got invalid uuid from mojang :()")
-                                        == info.uuid =>
-                                {
-                                    info!(
-                                        "User `{}` has uuid `{}` and username `{}`",
-                                        user_id, mojang_info.id, mojang_info.name
-                                    );
-
-                                    if let Some(session) = actor.connections.get_mut(&user_id) {
-                                        actor
-                                            .users
-                                            .entry(info.name.clone())
-                                            .or_insert(UserSession {
-                                                rate_limiter: RateLimiter::new(
-                                                    actor.config.message.clone(),
-                                                ),
-                                                connections: HashSet::new(),
-                                            })
-                                            .connections
-                                            .insert(user_id);
-
-                                        session.user = Some(info);
-
-                                        if let Err(err) =
-                                            session.addr.do_send(ClientPacket::Success {
-                                                reason: SuccessReason::Login,
-                                            })
-                                        {
-                                            info!(
-                                                "Could not send login success to `{}`: {}",
-                                                user_id, err
-                                            );
-                                        }
-                                    }
-                                }
-                                Ok(_) => {
-                                    let session = actor.connections.get(&user_id).unwrap();
-                                    send_login_failed(
-                                        user_id,
-                                        ClientError::InvalidId.into(),
-                                        &session.addr,
-                                        ctx,
-                                    )
-                                }
-                                Err(err) => {
-                                    let session = actor.connections.get(&user_id).unwrap();
-                                    send_login_failed(user_id, err, &session.addr, ctx)
-                                }
-                            }
-                            fut::ok(())
-                        })
-                        .spawn(ctx);
-                }
-                Err(err) => send_login_failed(user_id, err, &session.addr, ctx),
-            }
-        } else {
-            info!(
-                "User `{}` did not request mojang info, but tried to log in.",
-                user_id
-            );
-            session
-                .addr
-                .do_send(ClientPacket::Error {
-                    message: ClientError::MojangRequestMissing,
-                })
-                .ok();
-        }
+        let credentials = Credentials::Mojang {
+            name: info.name,
+            uuid: info.uuid,
+            session_hash: session.session_hash.clone(),
+        };
+        self.login(
+            user_id,
+            credentials,
+            info.allow_messages,
+            info.identifier,
+            ctx,
+        );
     }
 }
@@ -0,0 +1,50 @@
+use super::{ChatServer, ClientPacket};
+use crate::chat::InternalId;
+use crate::preferences::Preferences;
+
+use crate::error::*;
+use log::*;
+
+impl ChatServer {
+    pub(super) fn handle_set_preferences(&mut self, user_id: InternalId, preferences: Preferences) {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        let uuid = match &session.user {
+            Some(user) => user.uuid,
+            None => {
+                info!("`{}` is not logged in.", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotLoggedIn,
+                    })
+                    .ok();
+                return;
+            }
+        };
+
+        self.preferences.set(uuid, preferences.clone());
+        info!("User `{}` changed their preferences.", user_id);
+        self.send_to_user(&uuid, ClientPacket::Preferences { preferences });
+    }
+
+    /// Sends the preferences to a user who just logged in.
+    /// Users who never set their preferences get ones based on `allow_messages`.
+    pub(super) fn send_preferences(&self, user_id: InternalId) {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        let user = session.user.as_ref().expect("user should be logged in");
+
+        let preferences = self.preferences_of(&user.uuid);
+        if let Err(err) = session
+            .addr
+            .do_send(ClientPacket::Preferences { preferences })
+        {
+            warn!("Could not send preferences to `{}`: {}", user_id, err);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use super::{ChatServer, ClientPacket};
+use crate::audit::AuditAction;
+use crate::auth::UserInfo;
+use crate::chat::{InternalId, MessageId, SuccessReason};
+use crate::reports::ReportedMessage;
+
+use crate::error::*;
+use log::*;
+use uuid::Uuid;
+
+impl ChatServer {
+    pub(super) fn handle_report_message(
+        &mut self,
+        user_id: InternalId,
+        message_id: MessageId,
+        reason: String,
+    ) {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+
+        let message = match &session.user {
+            Some(info) => match self.history.get(message_id) {
+                // private messages may only be reported by their receiver or sender,
+                // group messages only by members of the group
+                Some(msg)
+                    if msg.author.uuid == info.uuid
+                        || msg.receiver.is_none_or(|receiver| receiver == info.uuid)
+                            && msg
+                                .group
+                                .is_none_or(|group| self.groups.get(group, &info.uuid).is_ok()) =>
+                {
+                    msg
+                }
+                _ => {
+                    info!(
+                        "User `{}` tried to report unknown message `{}`.",
+                        user_id, message_id
+                    );
+                    session
+                        .addr
+                        .do_send(ClientPacket::Error {
+                            message: ClientError::InvalidId,
+                        })
+                        .ok();
+                    return;
+                }
+            },
+            None => {
+                info!("`{}` is not logged in.", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotLoggedIn,
+                    })
+                    .ok();
+                return;
+            }
+        };
+
+        let user = message.author.uuid;
+        let message = ReportedMessage {
+            id: message.id,
+            author_info: message.author.clone(),
+            content: message.content.clone(),
+            private: message.receiver.is_some() || message.group.is_some(),
+        };
+        self.add_report(user_id, user, Some(message), reason);
+    }
+
+    pub(super) fn handle_report_user(&mut self, user_id: InternalId, user: Uuid, reason: String) {
+        self.add_report(user_id, user, None, reason);
+    }
+
+    fn add_report(
+        &mut self,
+        user_id: InternalId,
+        user: Uuid,
+        message: Option<ReportedMessage>,
+        reason: String,
+    ) {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        let reporter = match &session.user {
+            Some(info) => UserInfo {
+                name: info.name.clone(),
+                uuid: info.uuid,
+            },
+            None => {
+                info!("`{}` is not logged in.", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::NotLoggedIn,
+                    })
+                    .ok();
+                return;
+            }
+        };
+
+        let (limiter, reports) = (&mut self.report_limiter, &mut self.reports);
+        let res = self
+            .validator
+            .validate(&reason)
+            .and_then(|()| limiter.check(&reporter.uuid))
+            .and_then(|()| reports.add(reporter, user, message, reason));
+        match res {
+            Ok(report) => {
+                info!("User `{}` reported `{}`.", user_id, user);
+                session
+                    .addr
+                    .do_send(ClientPacket::Success {
+                        reason: SuccessReason::Report,
+                    })
+                    .ok();
+
+                let client_packet = ClientPacket::ReportReceived {
+                    report: report.clone(),
+                };
+                for session in self.connections.values() {
+                    match &session.user {
+                        Some(info) if self.moderation.is_moderator(&info.uuid) => {
+                            if let Err(err) = session.addr.do_send(client_packet.clone()) {
+                                warn!("Could not send report to moderator: {}", err);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Err(Error::AxoChat { source }) => {
+                info!("Could not add report of `{}`: {}", user_id, source);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error { message: source })
+                    .ok();
+            }
+            Err(err) => {
+                warn!("Could not add report of `{}`: {}", user_id, err);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::Internal,
+                    })
+                    .ok();
+            }
+        }
+    }
+
+    pub(super) fn handle_list_reports(&mut self, user_id: InternalId) {
+        if let Some(session) = self.moderator_session(user_id) {
+            let reports = self.reports.open().cloned().collect();
+            if let Err(err) = session.addr.do_send(ClientPacket::Reports { reports }) {
+                warn!("Could not send reports to user `{}`: {}", user_id, err);
+            }
+        }
+    }
+
+    pub(super) fn handle_resolve_report(&mut self, user_id: InternalId, id: u64) {
+        let (addr, moderator) = match self.moderator_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+
+        match self.reports.resolve(id, moderator) {
+            Ok(()) => {
+                info!("Report `{}` resolved by `{}`.", id, moderator);
+                self.audit(moderator, AuditAction::ResolveReport { id }, None);
+                addr.do_send(ClientPacket::Success {
+                    reason: SuccessReason::ResolveReport,
+                })
+                .ok();
+            }
+            Err(Error::AxoChat { source }) => {
+                info!("Could not resolve report `{}`: {}", id, source);
+                addr.do_send(ClientPacket::Error { message: source }).ok();
+            }
+            Err(err) => {
+                warn!("Could not resolve report `{}`: {}", id, err);
+                addr.do_send(ClientPacket::Error {
+                    message: ClientError::Internal,
+                })
+                .ok();
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::chat::{ChatServer, ClientPacket, InternalId, SuccessReason};
+use crate::error::*;
+use log::*;
+
+use rand::Rng;
+
+impl ChatServer {
+    /// Sends a new resume token to a connection which just logged in.
+    pub(super) fn send_resume_token(&mut self, user_id: InternalId) {
+        if !self.suspended.is_enabled() {
+            return;
+        }
+
+        let token = format!("{:032x}", self.rng.gen::<u128>());
+        let session = self
+            .connections
+            .get_mut(&user_id)
+            .expect("could not find connection");
+        session.resume_token = Some(token.clone());
+        if let Err(err) = session.addr.do_send(ClientPacket::ResumeToken { token }) {
+            warn!("Could not send resume token to `{}`: {}", user_id, err);
+        }
+    }
+
+    /// Lets a new connection take over the session of a disconnected one
+    /// and sends the messages it missed.
+    pub(super) fn handle_resume(&mut self, user_id: InternalId, token: &str) {
+        let session = self
+            .connections
+            .get(&user_id)
+            .expect("could not find connection");
+        if session.is_logged_in() {
+            info!("User `{}` tried to resume while logged in.", user_id);
+            session
+                .addr
+                .do_send(ClientPacket::Error {
+                    message: ClientError::AlreadyLoggedIn,
+                })
+                .ok();
+            return;
+        }
+
+        let (uuid, identifier) = match self.suspended.get(token) {
+            Some(suspended) => (suspended.user.uuid, suspended.user.identifier.clone()),
+            None => {
+                info!("User `{}` tried to resume an unknown session.", user_id);
+                session
+                    .addr
+                    .do_send(ClientPacket::Error {
+                        message: ClientError::LoginFailed,
+                    })
+                    .ok();
+                return;
+            }
+        };
+        if self
+            .moderation
+            .check_target_ban(session.ip, identifier.as_deref(), &uuid)
+        {
+            info!("User `{}` tried to resume while banned.", user_id);
+            session
+                .addr
+                .do_send(ClientPacket::Error {
+                    message: ClientError::Banned,
+                })
+                .ok();
+            return;
+        }
+        if !self.reserve_connection(&uuid) {
+            info!("User `{}` has too many connections.", user_id);
+            self.connections[&user_id]
+                .addr
+                .do_send(ClientPacket::Error {
+                    message: ClientError::TooManyConnections,
+                })
+                .ok();
+            return;
+        }
+
+        let suspended = self.suspended.take(token).unwrap();
+        info!(
+            "User `{}` resumed the session of `{}` with {} missed messages.",
+            user_id,
+            uuid,
+            suspended.buffered.len()
+        );
+        self.users.add(user_id, uuid, &suspended.user.name);
+
+        let session = self.connections.get_mut(&user_id).unwrap();
+        session.user = Some(suspended.user);
+        session.auth_method = Some(suspended.auth_method);
+        if let Err(err) = session.addr.do_send(ClientPacket::Success {
+            reason: SuccessReason::Resume,
+        }) {
+            info!("Could not send resume success to `{}`: {}", user_id, err);
+        }
+        for packet in suspended.buffered {
+            if let Err(err) = session.addr.do_send(packet) {
+                warn!("Could not send missed message to `{}`: {}", user_id, err);
+            }
+        }
+        self.send_resume_token(user_id);
+        self.send_chat_mode(user_id);
+        self.send_preferences(user_id);
+    }
+}
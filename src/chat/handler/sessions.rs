@@ -0,0 +1,51 @@
+use crate::chat::{ChatServer, ClientPacket, InternalId, SuccessReason};
+use crate::error::*;
+use log::*;
+
+impl ChatServer {
+    pub(super) fn handle_list_sessions(&mut self, user_id: InternalId) {
+        let session = match self.logged_in_session(user_id) {
+            Some(session) => session,
+            None => return,
+        };
+        let uuid = session.user.as_ref().unwrap().uuid;
+
+        if let Err(err) = session.addr.do_send(ClientPacket::Sessions {
+            current: user_id,
+            sessions: self.connection_details(&uuid),
+        }) {
+            warn!("Could not send sessions to `{}`: {}", user_id, err);
+        }
+    }
+
+    /// Logs out another connection of the same user, e.g. because it was stolen.
+    pub(super) fn handle_terminate_session(&mut self, user_id: InternalId, id: InternalId) {
+        let (addr, uuid) = match self.logged_in_session(user_id) {
+            Some(session) => (session.addr.clone(), session.user.as_ref().unwrap().uuid),
+            None => return,
+        };
+
+        let owned = self
+            .connection_details(&uuid)
+            .iter()
+            .any(|connection| connection.id == id);
+        if !owned {
+            info!(
+                "User `{}` tried to terminate unknown session `{}`.",
+                user_id, id
+            );
+            addr.do_send(ClientPacket::Error {
+                message: ClientError::InvalidId,
+            })
+            .ok();
+            return;
+        }
+
+        info!("User `{}` terminated session `{}`.", user_id, id);
+        self.terminate_session(id, ClientError::SessionTerminated);
+        addr.do_send(ClientPacket::Success {
+            reason: SuccessReason::TerminateSession,
+        })
+        .ok();
+    }
+}
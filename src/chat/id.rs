@@ -21,6 +21,44 @@ impl fmt::Display for InternalId {
     }
 }
 
+/// The id of a chat message.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(transparent)]
+pub struct MessageId(u64);
+
+impl MessageId {
+    pub fn new(id: u64) -> MessageId {
+        MessageId(id)
+    }
+}
+
+impl fmt::Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The id of a group conversation.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[serde(transparent)]
+pub struct GroupId(u64);
+
+impl GroupId {
+    pub fn new(id: u64) -> GroupId {
+        GroupId(id)
+    }
+
+    pub fn next(self) -> GroupId {
+        GroupId(self.0 + 1)
+    }
+}
+
+impl fmt::Display for GroupId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl<A, M> MessageResponse<A, M> for InternalId
 where
     A: Actor,
@@ -1,11 +1,16 @@
 mod connect;
 mod handler;
 mod id;
+mod resume;
 mod session;
+mod users;
 
 pub use id::*;
+use resume::*;
+use session::Terminate;
+use users::*;
 
-use crate::config::Config;
+use crate::config::{ChannelLimits, Config, NetConfig, SessionPolicy};
 use crate::error::*;
 use log::*;
 
@@ -14,21 +19,40 @@ use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use serde::{Deserialize, Serialize};
 
-use crate::auth::{Authenticator, UserInfo};
-use crate::message::{MessageValidator, RateLimiter};
-use crate::moderation::Moderation;
+use crate::audit::{AuditEntry, AuditFilter, AuditLog};
+use crate::auth::{AuthStats, Authenticator, SessionServer, UserInfo};
+use crate::directory::{Directory, KnownName};
+use crate::filter::Filter;
+use crate::friends::Friends;
+use crate::groups::{Group, Groups};
+use crate::limit::{ActionLimiter, PacketLimiter};
+use crate::links::LinkFilter;
+use crate::message::{ChatMode, MessageHistory, MessageValidator, StoredMessage};
+use crate::moderation::{BanTarget, Moderation, TargetBan};
+use crate::preferences::{PreferenceStore, Preferences, Privacy};
+use crate::provider::{providers, AuthMethod, AuthProvider};
+use crate::reports::{Report, Reports};
+use crate::spam::SpamDetector;
+use crate::time::unix_secs;
 use rand::{rngs::OsRng, SeedableRng};
 use rand_hc::Hc128Rng;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
 use uuid::Uuid;
 
 pub fn chat_route(
     req: HttpRequest,
     stream: web::Payload,
     srv: web::Data<Addr<ChatServer>>,
+    net: web::Data<NetConfig>,
 ) -> actix_web::Result<HttpResponse> {
+    let ip = crate::ip::client_ip(&req, &net.trusted_proxies);
     ws::start(
-        session::Session::new(InternalId::new(0), srv.get_ref().clone()),
+        session::Session::new(InternalId::new(0), ip, srv.get_ref().clone()),
         &req,
         stream,
     )
@@ -36,40 +60,131 @@ pub fn chat_route(
 
 pub struct ChatServer {
     connections: HashMap<InternalId, SessionState>,
-    users: HashMap<String, UserSession>,
+    users: Users,
+    ip_connections: HashMap<IpAddr, usize>,
+    suspended: Suspended,
 
     rng: rand_hc::Hc128Rng,
-    authenticator: Option<Authenticator>,
+    authenticator: Option<Rc<Authenticator>>,
+    providers: HashMap<AuthMethod, Box<dyn AuthProvider>>,
+    session_server: Rc<SessionServer>,
     validator: MessageValidator,
+    history: MessageHistory,
+    mode: ChatMode,
+    spam: SpamDetector,
+    filter: Filter,
+    links: LinkFilter,
     moderation: Moderation,
+    reports: Reports,
+    report_limiter: ActionLimiter,
+    friend_limiter: ActionLimiter,
+    group_limiter: ActionLimiter,
+    audit: AuditLog,
+    directory: Directory,
+    preferences: PreferenceStore,
+    friends: Friends,
+    groups: Groups,
     config: Config,
 
     current_internal_user_id: u64,
+    current_message_id: u64,
 }
 
 impl ChatServer {
     pub fn new(config: Config) -> ChatServer {
+        let authenticator = config.auth.as_ref().map(|auth| {
+            Rc::new(Authenticator::new(auth).expect("could not initialize authenticator"))
+        });
+        let session_server = Rc::new(
+            SessionServer::new(&config.mojang).expect("could not initialize session server"),
+        );
+        let providers = providers(&config, authenticator.clone(), session_server.clone());
+
         ChatServer {
             connections: HashMap::new(),
-            users: HashMap::new(),
+            users: Users::new(),
+            ip_connections: HashMap::new(),
+            suspended: Suspended::new(config.resume.clone()),
 
             rng: Hc128Rng::from_rng(OsRng).expect("could not initialize hc128 rng"),
-            authenticator: config
-                .auth
-                .as_ref()
-                .map(|auth| Authenticator::new(&auth).expect("could not initialize authenticator")),
+            authenticator,
+            providers,
+            session_server,
             validator: MessageValidator::new(config.message.clone()),
+            history: MessageHistory::new(config.message.history_size),
+            mode: ChatMode::default(),
+            spam: SpamDetector::new(config.message.spam.clone(), *config.message.count_duration),
+            filter: Filter::new(config.filter.clone()).expect("could not read filter rules"),
+            links: LinkFilter::new(config.links.clone()),
             moderation: Moderation::new(config.moderation.clone())
                 .expect("could not start moderation"),
+            reports: Reports::new(config.moderation.reports.clone())
+                .expect("could not read reports"),
+            report_limiter: ActionLimiter::new(config.limits.reports),
+            friend_limiter: ActionLimiter::new(config.limits.friend_requests),
+            group_limiter: ActionLimiter::new(config.limits.groups),
+            audit: AuditLog::new(config.moderation.audit_log.clone())
+                .expect("could not read audit log"),
+            directory: Directory::new(config.directory.clone())
+                .expect("could not read user directory"),
+            preferences: PreferenceStore::new(config.directory.preferences.clone())
+                .expect("could not read preferences"),
+            friends: Friends::new(config.directory.friends.clone())
+                .expect("could not read friends"),
+            groups: Groups::new(
+                config.directory.groups.clone(),
+                config.message.max_group_members,
+                config.message.max_groups_per_user,
+            )
+            .expect("could not read groups"),
             config,
 
             current_internal_user_id: 0,
+            current_message_id: 0,
         }
     }
 }
 
+/// The interval in which changes of reports, friends, groups and preferences are written.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the statistics of the session server are logged.
+const AUTH_STATS_INTERVAL: Duration = Duration::from_secs(60);
+
 impl Actor for ChatServer {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(SAVE_INTERVAL, |actor, _ctx| actor.save_changes());
+        ctx.run_interval(AUTH_STATS_INTERVAL, |actor, _ctx| {
+            actor.session_server.log_stats();
+        });
+
+        ctx.run_interval(*self.config.filter.reload_interval, |actor, _ctx| {
+            actor.filter.reload_if_modified();
+        });
+
+        if let Some(auth) = self.config.auth.as_ref().filter(|auth| auth.keys.is_some()) {
+            ctx.run_interval(*auth.key_reload_interval, |actor, _ctx| {
+                if let Some(authenticator) = &actor.authenticator {
+                    authenticator.reload_keys();
+                }
+            });
+        }
+
+        if self.suspended.is_enabled() {
+            ctx.run_interval(*self.config.resume.grace_period, |actor, _ctx| {
+                for uuid in actor.suspended.expire() {
+                    debug!("Sessions of `{}` can no longer be resumed.", uuid);
+                    actor.notify_logout(&uuid);
+                }
+            });
+        }
+    }
+
+    fn stopped(&mut self, _ctx: &mut Context<Self>) {
+        self.save_changes();
+    }
 }
 
 impl Handler<Disconnect> for ChatServer {
@@ -78,24 +193,34 @@ impl Handler<Disconnect> for ChatServer {
     fn handle(&mut self, msg: Disconnect, _ctx: &mut Context<Self>) {
         info!("User `{}` disconnected.", msg.id);
         if let Some(session) = self.connections.remove(&msg.id) {
-            if let Some(info) = session.user {
-                let user_session = self
-                    .users
-                    .get_mut(&info.name)
-                    .expect("the ids should still exist here");
-                user_session.connections.remove(&msg.id);
-                if user_session.connections.is_empty() {
-                    self.users.remove(&info.name);
+            if let Some(ip) = session.ip {
+                if let Some(count) = self.ip_connections.get_mut(&ip) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.ip_connections.remove(&ip);
+                    }
                 }
             }
+            if let Some(uuid) = session.user.as_ref().map(|user| user.uuid) {
+                self.users.remove(msg.id, &uuid);
+                self.suspended.suspend(msg.id, session);
+                self.notify_logout(&uuid);
+            }
         }
     }
 }
 
 pub(self) struct SessionState {
     addr: Recipient<ClientPacket>,
+    close: Recipient<Terminate>,
+    ip: Option<IpAddr>,
+    connected: SystemTime,
     session_hash: Option<String>,
     user: Option<User>,
+    auth_method: Option<AuthMethod>,
+    /// The token which can be used to resume the session after a disconnect.
+    resume_token: Option<String>,
+    packet_limiter: PacketLimiter,
 }
 
 impl SessionState {
@@ -104,9 +229,220 @@ impl SessionState {
     }
 }
 
-struct UserSession {
-    rate_limiter: RateLimiter,
-    connections: HashSet<InternalId>,
+impl ChatServer {
+    /// Writes the stores which were changed since they were last written.
+    fn save_changes(&mut self) {
+        if let Err(err) = self.reports.save_if_changed() {
+            warn!("Could not save reports: {}", err);
+        }
+        if let Err(err) = self.preferences.save_if_changed() {
+            warn!("Could not save preferences: {}", err);
+        }
+        if let Err(err) = self.friends.save_if_changed() {
+            warn!("Could not save friends: {}", err);
+        }
+        if let Err(err) = self.groups.save_if_changed() {
+            warn!("Could not save groups: {}", err);
+        }
+    }
+
+    /// Returns whether the user with the given `uuid` may log in with another connection.
+    /// If the user has too many connections and the policy is to evict the oldest ones,
+    /// they are terminated to make room for the new one.
+    fn reserve_connection(&mut self, uuid: &Uuid) -> bool {
+        let max = self.config.limits.max_connections_per_user;
+        let mut connections: Vec<(SystemTime, InternalId)> = self
+            .users
+            .get(uuid)
+            .into_iter()
+            .flat_map(|user| &user.connections)
+            .filter_map(|id| Some((self.connections.get(id)?.connected, *id)))
+            .collect();
+        if connections.len() < max {
+            return true;
+        }
+
+        match self.config.limits.session_policy {
+            SessionPolicy::Reject => false,
+            SessionPolicy::EvictOldest => {
+                connections.sort_by_key(|(connected, _)| *connected);
+                let evicted = connections.len() + 1 - max;
+                for (_, id) in connections.into_iter().take(evicted) {
+                    info!("Evicting connection `{}` of `{}`.", id, uuid);
+                    self.terminate_session(id, ClientError::SessionTerminated);
+                }
+                max > 0
+            }
+        }
+    }
+
+    /// Logs out the connection with the given `id` and closes it.
+    /// If the connection is already closed, its session can no longer be resumed.
+    fn terminate_session(&mut self, id: InternalId, reason: ClientError) {
+        let uuid = match self.connections.get_mut(&id) {
+            Some(session) => {
+                session.resume_token = None;
+                session.auth_method = None;
+                let user = session.user.take();
+                session
+                    .addr
+                    .do_send(ClientPacket::Error { message: reason })
+                    .ok();
+                session.close.do_send(Terminate).ok();
+                user.map(|user| user.uuid)
+            }
+            None => self.suspended.remove(id).map(|session| session.user.uuid),
+        };
+
+        if let Some(uuid) = uuid {
+            self.users.remove(id, &uuid);
+            self.notify_logout(&uuid);
+        }
+    }
+
+    /// Returns the open connections of a user and their sessions which can be resumed.
+    fn connection_details(&self, uuid: &Uuid) -> Vec<ConnectionDetails> {
+        let open = self
+            .users
+            .get(uuid)
+            .into_iter()
+            .flat_map(|user| &user.connections)
+            .filter_map(|id| {
+                let session = self.connections.get(id)?;
+                Some(ConnectionDetails {
+                    id: *id,
+                    ip: session.ip,
+                    connected: unix_secs(session.connected),
+                    auth_method: session.auth_method,
+                    identifier: session.user.as_ref()?.identifier.clone(),
+                    suspended: false,
+                })
+            });
+        let suspended = self
+            .suspended
+            .of_user(uuid)
+            .map(|session| ConnectionDetails {
+                id: session.id,
+                ip: session.ip,
+                connected: unix_secs(session.connected),
+                auth_method: Some(session.auth_method),
+                identifier: session.user.identifier.clone(),
+                suspended: true,
+            });
+
+        let mut connections: Vec<ConnectionDetails> = open.chain(suspended).collect();
+        connections.sort_by_key(|connection| connection.connected);
+        connections
+    }
+
+    fn next_message_id(&mut self) -> MessageId {
+        self.current_message_id += 1;
+        MessageId::new(self.current_message_id)
+    }
+
+    /// Returns the name and uuid of a user.
+    /// If the user is not logged in, the name they were last seen with is used.
+    fn user_info(&self, uuid: &Uuid) -> UserInfo {
+        let name = self
+            .users
+            .get(uuid)
+            .map(|user| user.name.clone())
+            .or_else(|| {
+                let known = self.directory.names(uuid).last()?;
+                Some(known.name.clone())
+            })
+            .unwrap_or_default();
+        UserInfo { name, uuid: *uuid }
+    }
+
+    /// Sends `packet` to every connection of the user with the given `uuid`.
+    fn send_to_user(&self, uuid: &Uuid, packet: ClientPacket) {
+        for session in self
+            .users
+            .get(uuid)
+            .into_iter()
+            .flat_map(|user| user.connections.iter())
+            .filter_map(|id| self.connections.get(id))
+        {
+            if let Err(err) = session.addr.do_send(packet.clone()) {
+                warn!("Could not send packet to user `{}`: {}", uuid, err);
+            }
+        }
+    }
+
+    /// Tells the friends of a user that they went offline,
+    /// unless the user is still connected or may resume a session.
+    fn notify_logout(&self, uuid: &Uuid) {
+        if self.users.get(uuid).is_none() && !self.suspended.contains(uuid) {
+            self.notify_friends(uuid, false);
+        }
+    }
+
+    /// Sends the presence of a user to all of their friends.
+    fn notify_friends(&self, uuid: &Uuid, online: bool) {
+        let user = self.user_info(uuid);
+        for friend in self.friends.friends(uuid) {
+            let client_packet = ClientPacket::FriendStatus {
+                user: user.clone(),
+                online,
+            };
+            self.send_to_user(friend, client_packet);
+        }
+    }
+
+    /// Finds a user by their uuid or name.
+    /// Users which are not logged in are looked up in the directory.
+    fn resolve_user(&self, user: &str) -> Option<Uuid> {
+        self.users
+            .find(user)
+            .or_else(|| user.parse().ok())
+            .or_else(|| self.directory.find_by_name(user))
+    }
+
+    /// Returns whether `receiver` accepts private messages from `sender`.
+    fn accepts_messages(&self, receiver: &Uuid, sender: &Uuid) -> bool {
+        match self.preferences_of(receiver).privacy {
+            Privacy::Everyone => true,
+            Privacy::FriendsOnly => self.friends.are_friends(receiver, sender),
+            Privacy::ModeratorsOnly => self.moderation.is_moderator(sender),
+            Privacy::Nobody => false,
+        }
+    }
+
+    /// Returns the preferences of a user.
+    /// Users without saved preferences, like anonymous users, only accept private messages
+    /// if they allowed them when logging in.
+    fn preferences_of(&self, uuid: &Uuid) -> Preferences {
+        if self.preferences.contains(uuid) {
+            return self.preferences.get(uuid);
+        }
+
+        let allow_messages = self
+            .users
+            .get(uuid)
+            .into_iter()
+            .flat_map(|user| &user.connections)
+            .filter_map(|id| self.connections.get(id)?.user.as_ref())
+            .any(|user| user.allow_messages);
+        let privacy = if allow_messages {
+            Privacy::Everyone
+        } else {
+            Privacy::Nobody
+        };
+        Preferences { privacy }
+    }
+
+    /// Returns the message rate limits which apply to the user with the given `uuid`.
+    fn rate_limits(&self, uuid: &Uuid) -> ChannelLimits {
+        let cfg = &self.config.message.rate_limit;
+        if self.moderation.is_moderator(uuid) {
+            cfg.moderator
+        } else if self.directory.is_new(uuid, *cfg.new_account_duration) {
+            cfg.new_account
+        } else {
+            cfg.default
+        }
+    }
 }
 
 #[derive(Message)]
@@ -123,19 +459,94 @@ enum ClientPacket {
     },
     NewJWT {
         token: String,
+        refresh_token: String,
+    },
+    ResumeToken {
+        token: String,
     },
     Message {
+        id: MessageId,
         author_info: UserInfo,
         content: String,
+        /// Only sent to moderators if the author is shadow banned.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        shadow_banned: bool,
     },
     PrivateMessage {
+        id: MessageId,
+        author_info: UserInfo,
+        content: String,
+        /// Only sent to moderators if the author is shadow banned.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        shadow_banned: bool,
+    },
+    GroupMessage {
+        id: MessageId,
+        group: GroupId,
+        author_info: UserInfo,
+        content: String,
+        /// Only sent to moderators if the author is shadow banned.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        shadow_banned: bool,
+    },
+    GroupChanged {
+        group: Group,
+    },
+    GroupRemoved {
+        group: GroupId,
+    },
+    Groups {
+        groups: Vec<Group>,
+    },
+    MessageFlagged {
         author_info: UserInfo,
         content: String,
+        flagged: Vec<String>,
     },
     UserCount {
         connections: u32,
         logged_in: u32,
     },
+    AuthStats(AuthStats),
+    Sessions {
+        /// The id of the connection receiving this packet.
+        current: InternalId,
+        sessions: Vec<ConnectionDetails>,
+    },
+    ReportReceived {
+        report: Report,
+    },
+    Reports {
+        reports: Vec<Report>,
+    },
+    AuditLog {
+        entries: Vec<AuditEntry>,
+    },
+    TargetBans {
+        bans: Vec<TargetBan>,
+    },
+    UserDetails(UserDetails),
+    Preferences {
+        preferences: Preferences,
+    },
+    FriendRequest {
+        user: UserInfo,
+    },
+    FriendStatus {
+        user: UserInfo,
+        online: bool,
+    },
+    FriendRemoved {
+        user: Uuid,
+    },
+    Friends {
+        friends: Vec<FriendInfo>,
+        incoming: Vec<UserInfo>,
+        outgoing: Vec<UserInfo>,
+    },
+    ChatModeChanged {
+        mode: ChatMode,
+    },
     Success {
         reason: SuccessReason,
     },
@@ -150,13 +561,121 @@ enum ClientPacket {
 enum ServerPacket {
     RequestMojangInfo,
     LoginMojang(User),
-    LoginJWT { token: String, allow_messages: bool },
+    LoginJWT {
+        token: String,
+        allow_messages: bool,
+        #[serde(default)]
+        identifier: Option<String>,
+    },
     RequestJWT,
-    Message { content: String },
-    PrivateMessage { receiver: String, content: String },
-    BanUser { user: Uuid },
-    UnbanUser { user: Uuid },
+    LoginMinecraft {
+        token: String,
+        allow_messages: bool,
+        #[serde(default)]
+        identifier: Option<String>,
+    },
+    LoginAnonymous {
+        allow_messages: bool,
+        #[serde(default)]
+        identifier: Option<String>,
+    },
+    Resume {
+        token: String,
+    },
+    RefreshJWT {
+        refresh_token: String,
+    },
+    RevokeTokens {
+        #[serde(default)]
+        user: Option<Uuid>,
+    },
+    Message {
+        content: String,
+    },
+    PrivateMessage {
+        receiver: String,
+        content: String,
+    },
+    BanUser {
+        user: Uuid,
+        #[serde(default)]
+        shadow: bool,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    UnbanUser {
+        user: Uuid,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    BanTarget {
+        target: BanTarget,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    UnbanTarget {
+        target: BanTarget,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    ListTargetBans,
+    SetPreferences(Preferences),
+    SendFriendRequest {
+        user: String,
+    },
+    AcceptFriendRequest {
+        user: Uuid,
+    },
+    RemoveFriend {
+        user: Uuid,
+    },
+    ListFriends,
+    CreateGroup {
+        name: String,
+        members: Vec<Uuid>,
+    },
+    GroupMessage {
+        group: GroupId,
+        content: String,
+    },
+    AddGroupMember {
+        group: GroupId,
+        user: Uuid,
+    },
+    RemoveGroupMember {
+        group: GroupId,
+        user: Uuid,
+    },
+    LeaveGroup {
+        group: GroupId,
+    },
+    ListGroups,
+    LookupUser {
+        #[serde(default)]
+        uuid: Option<Uuid>,
+        #[serde(default)]
+        name: Option<String>,
+    },
     RequestUserCount,
+    RequestAuthStats,
+    ListSessions,
+    TerminateSession {
+        id: InternalId,
+    },
+    ReportMessage {
+        message_id: MessageId,
+        reason: String,
+    },
+    ReportUser {
+        user: Uuid,
+        reason: String,
+    },
+    ListReports,
+    ResolveReport {
+        id: u64,
+    },
+    RequestAuditLog(AuditFilter),
+    SetChatMode(ChatMode),
 }
 
 #[derive(Message)]
@@ -170,12 +689,63 @@ struct User {
     pub name: String,
     pub uuid: Uuid,
     /// Should this user allow private messages?
+    /// This is only used if the user did not set their preferences yet.
     pub allow_messages: bool,
+    /// An identifier of the client, which can be banned.
+    #[serde(default)]
+    pub identifier: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct FriendInfo {
+    #[serde(flatten)]
+    user: UserInfo,
+    online: bool,
+}
+
+/// Everything moderators can look up about a user.
+#[derive(Serialize, Clone)]
+struct UserDetails {
+    uuid: Uuid,
+    /// The names of the user, oldest first.
+    names: Vec<KnownName>,
+    /// The time the user was first seen in seconds since the unix epoch.
+    first_seen: Option<u64>,
+    connections: Vec<ConnectionDetails>,
+    moderator: bool,
+    banned: bool,
+    shadow_banned: bool,
+    reports: usize,
+    open_reports: usize,
+    /// The most recent messages of the user, newest first.
+    messages: Vec<StoredMessage>,
+}
+
+#[derive(Serialize, Clone)]
+struct ConnectionDetails {
+    id: InternalId,
+    ip: Option<IpAddr>,
+    /// The time of the connection in seconds since the unix epoch.
+    connected: u64,
+    auth_method: Option<AuthMethod>,
+    /// The identifier the client sent when logging in.
+    identifier: Option<String>,
+    /// Whether the connection was closed, but its session can still be resumed.
+    suspended: bool,
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone)]
 enum SuccessReason {
     Login,
     Ban,
+    ShadowBan,
     Unban,
+    Report,
+    ResolveReport,
+    FriendRequest,
+    AcceptFriendRequest,
+    RemoveFriend,
+    RevokeTokens,
+    Resume,
+    TerminateSession,
 }
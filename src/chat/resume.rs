@@ -0,0 +1,167 @@
+use super::{ClientPacket, InternalId, SessionState, User};
+use crate::config::ResumeConfig;
+use crate::provider::AuthMethod;
+
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    net::IpAddr,
+    time::{Duration, Instant, SystemTime},
+};
+use uuid::Uuid;
+
+/// The session of a disconnected user, which may be resumed by a new connection.
+pub(super) struct SuspendedSession {
+    /// The id of the closed connection.
+    pub id: InternalId,
+    pub ip: Option<IpAddr>,
+    pub connected: SystemTime,
+    pub user: User,
+    pub auth_method: AuthMethod,
+    /// The messages the user received while disconnected, oldest first.
+    pub buffered: VecDeque<ClientPacket>,
+    disconnected: Instant,
+}
+
+/// The disconnected sessions by their resume token.
+pub(super) struct Suspended {
+    cfg: ResumeConfig,
+    sessions: HashMap<String, SuspendedSession>,
+}
+
+impl Suspended {
+    pub fn new(cfg: ResumeConfig) -> Suspended {
+        Suspended {
+            cfg,
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Returns whether sessions can be resumed.
+    pub fn is_enabled(&self) -> bool {
+        *self.cfg.grace_period > Duration::from_secs(0)
+    }
+
+    /// Keeps the session of a closed connection, so that it can be resumed with its resume token.
+    /// Sessions which were not logged in or did not receive a resume token are dropped.
+    /// If the user has too many disconnected sessions, the oldest ones are dropped.
+    pub fn suspend(&mut self, id: InternalId, session: SessionState) {
+        if let (Some(token), Some(user), Some(auth_method)) =
+            (session.resume_token, session.user, session.auth_method)
+        {
+            let mut sessions: Vec<(Instant, String)> = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.user.uuid == user.uuid)
+                .map(|(token, session)| (session.disconnected, token.clone()))
+                .collect();
+            sessions.sort();
+            let dropped = (sessions.len() + 1).saturating_sub(self.cfg.max_suspended_per_user);
+            for (_, token) in sessions.into_iter().take(dropped) {
+                self.sessions.remove(&token);
+            }
+            if self.cfg.max_suspended_per_user == 0 {
+                return;
+            }
+
+            self.sessions.insert(
+                token,
+                SuspendedSession {
+                    id,
+                    ip: session.ip,
+                    connected: session.connected,
+                    user,
+                    auth_method,
+                    buffered: VecDeque::new(),
+                    disconnected: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Returns the session with the given resume token, if it has not expired yet.
+    pub fn get(&self, token: &str) -> Option<&SuspendedSession> {
+        self.sessions
+            .get(token)
+            .filter(|session| session.disconnected.elapsed() < *self.cfg.grace_period)
+    }
+
+    /// Removes the session with the given resume token.
+    pub fn take(&mut self, token: &str) -> Option<SuspendedSession> {
+        self.sessions.remove(token)
+    }
+
+    /// Removes the session of the closed connection with the given `id`.
+    pub fn remove(&mut self, id: InternalId) -> Option<SuspendedSession> {
+        let token = self
+            .sessions
+            .iter()
+            .find(|(_, session)| session.id == id)
+            .map(|(token, _)| token.clone())?;
+        self.sessions.remove(&token)
+    }
+
+    /// Returns the sessions of the user with the given `uuid` which can still be resumed.
+    pub fn of_user<'a>(&'a self, uuid: &'a Uuid) -> impl Iterator<Item = &'a SuspendedSession> {
+        let grace_period = *self.cfg.grace_period;
+        self.sessions.values().filter(move |session| {
+            session.user.uuid == *uuid && session.disconnected.elapsed() < grace_period
+        })
+    }
+
+    /// Removes the sessions which can no longer be resumed and returns their users.
+    pub fn expire(&mut self) -> BTreeSet<Uuid> {
+        let grace_period = *self.cfg.grace_period;
+        let expired: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.disconnected.elapsed() >= grace_period)
+            .map(|(token, _)| token.clone())
+            .collect();
+        expired
+            .iter()
+            .filter_map(|token| self.sessions.remove(token))
+            .map(|session| session.user.uuid)
+            .collect()
+    }
+
+    /// Returns whether the user with the given `uuid` has a session which can be resumed.
+    pub fn contains(&self, uuid: &Uuid) -> bool {
+        self.sessions
+            .values()
+            .any(|session| session.user.uuid == *uuid)
+    }
+
+    /// Finds a disconnected user by their uuid or their name, ignoring case.
+    pub fn find(&self, user: &str) -> Option<Uuid> {
+        let uuid = user.parse().ok();
+        self.sessions
+            .values()
+            .map(|session| &session.user)
+            .find(|info| Some(info.uuid) == uuid || info.name.eq_ignore_ascii_case(user))
+            .map(|info| info.uuid)
+    }
+
+    /// Buffers a message for every disconnected session of the user with the given `uuid`.
+    pub fn send_to_user(&mut self, uuid: &Uuid, packet: &ClientPacket) {
+        self.broadcast(|user| {
+            if user.uuid == *uuid {
+                Some(packet)
+            } else {
+                None
+            }
+        });
+    }
+
+    /// Buffers the message returned by `packet_for` for every disconnected session.
+    pub fn broadcast<'a>(&mut self, mut packet_for: impl FnMut(&User) -> Option<&'a ClientPacket>) {
+        let max = self.cfg.max_buffered_messages;
+        for session in self.sessions.values_mut() {
+            if let Some(packet) = packet_for(&session.user) {
+                session.buffered.push_back(packet.clone());
+                if session.buffered.len() > max {
+                    session.buffered.pop_front();
+                }
+            }
+        }
+    }
+}
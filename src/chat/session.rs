@@ -7,15 +7,17 @@ use log::*;
 
 use actix::*;
 use actix_web_actors::ws;
+use std::net::IpAddr;
 
 pub struct Session {
     id: InternalId,
+    ip: Option<IpAddr>,
     addr: Addr<ChatServer>,
 }
 
 impl Session {
-    pub fn new(id: InternalId, addr: Addr<ChatServer>) -> Session {
-        Session { id, addr }
+    pub fn new(id: InternalId, ip: Option<IpAddr>, addr: Addr<ChatServer>) -> Session {
+        Session { id, ip, addr }
     }
 }
 
@@ -23,16 +25,28 @@ impl Actor for Session {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        let addr = ctx.address();
         self.addr
-            .send(Connect::new(ctx.address().recipient()))
+            .send(Connect::new(
+                addr.clone().recipient(),
+                addr.recipient(),
+                self.ip,
+            ))
             .into_actor(self)
-            .then(|res, actor, _ctx| {
+            .then(|res, actor, ctx| {
                 match res {
-                    Ok(id) => {
+                    Ok(Ok(id)) => {
                         actor.id = id;
                     }
+                    Ok(Err(message)) => {
+                        let msg = serde_json::to_string(&ClientPacket::Error { message })
+                            .expect("could not encode message");
+                        ctx.text(msg);
+                        ctx.stop();
+                    }
                     Err(err) => {
                         warn!("Could not accept connection: {}", err);
+                        ctx.stop();
                     }
                 }
                 fut::ok(())
@@ -93,3 +107,17 @@ impl Handler<ClientPacket> for Session {
         ctx.text(msg);
     }
 }
+
+/// Closes the connection, e.g. because its session was terminated.
+#[derive(Message)]
+pub(super) struct Terminate;
+
+impl Handler<Terminate> for Session {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Terminate, ctx: &mut Self::Context) {
+        info!("Closing connection `{}`.", self.id);
+        ctx.close(None);
+        ctx.stop();
+    }
+}
@@ -0,0 +1,84 @@
+use super::InternalId;
+use crate::message::RateLimiter;
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// The state of a logged in user, shared by all of their connections.
+pub(super) struct UserSession {
+    pub rate_limiter: RateLimiter,
+    pub connections: HashSet<InternalId>,
+    /// The name the user most recently logged in with.
+    pub name: String,
+}
+
+/// The logged in users, by uuid and by name.
+///
+/// Names are case-insensitive. If a user logs in with a new name, the old one is released.
+pub(super) struct Users {
+    sessions: HashMap<Uuid, UserSession>,
+    names: HashMap<String, Uuid>,
+}
+
+impl Users {
+    pub fn new() -> Users {
+        Users {
+            sessions: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Registers a new connection of the user with the given `uuid`.
+    pub fn add(&mut self, id: InternalId, uuid: Uuid, name: &str) {
+        let session = self.sessions.entry(uuid).or_insert_with(|| UserSession {
+            rate_limiter: RateLimiter::new(),
+            connections: HashSet::new(),
+            name: name.to_owned(),
+        });
+        session.connections.insert(id);
+
+        if session.name != name {
+            let old = std::mem::replace(&mut session.name, name.to_owned());
+            let old = old.to_lowercase();
+            if self.names.get(&old) == Some(&uuid) {
+                self.names.remove(&old);
+            }
+        }
+        self.names.insert(name.to_lowercase(), uuid);
+    }
+
+    /// Removes a connection of the user with the given `uuid`.
+    /// The user is removed once all of their connections are.
+    pub fn remove(&mut self, id: InternalId, uuid: &Uuid) {
+        if let Some(session) = self.sessions.get_mut(uuid) {
+            session.connections.remove(&id);
+            if session.connections.is_empty() {
+                let name = session.name.to_lowercase();
+                self.sessions.remove(uuid);
+                if self.names.get(&name) == Some(uuid) {
+                    self.names.remove(&name);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<&UserSession> {
+        self.sessions.get(uuid)
+    }
+
+    pub fn get_mut(&mut self, uuid: &Uuid) -> Option<&mut UserSession> {
+        self.sessions.get_mut(uuid)
+    }
+
+    /// Finds a user by their uuid or their name, ignoring case.
+    pub fn find(&self, user: &str) -> Option<Uuid> {
+        match user.parse() {
+            Ok(uuid) if self.sessions.contains_key(&uuid) => Some(uuid),
+            _ => self.names.get(&user.to_lowercase()).copied(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+}
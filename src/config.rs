@@ -1,4 +1,5 @@
 use crate::error::*;
+use crate::moderation::Role;
 use jsonwebtoken::Algorithm;
 use serde::{
     de::{self, Deserializer, Visitor},
@@ -9,7 +10,7 @@ use std::{
     env, fmt,
     fs::{self, File},
     io::{self, Read},
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     ops::Deref,
     path::PathBuf,
     time::Duration,
@@ -26,6 +27,24 @@ pub struct Config {
     #[serde(default)]
     pub moderation: ModConfig,
 
+    #[serde(default)]
+    pub limits: LimitConfig,
+
+    #[serde(default)]
+    pub directory: DirectoryConfig,
+
+    #[serde(default)]
+    pub filter: FilterConfig,
+
+    #[serde(default)]
+    pub links: LinkConfig,
+
+    #[serde(default)]
+    pub resume: ResumeConfig,
+
+    #[serde(default)]
+    pub mojang: MojangConfig,
+
     pub auth: Option<AuthConfig>,
 }
 
@@ -39,6 +58,10 @@ pub struct NetConfig {
     /// The SSL key file.
     /// If the extension is `pem`, `PEM` format will be used, otherwise `ASN1`.
     pub key_file: Option<PathBuf>,
+
+    /// Proxies whose `X-Forwarded-For` header is trusted.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl Default for NetConfig {
@@ -47,6 +70,7 @@ impl Default for NetConfig {
             address: ([127, 0, 0, 1], 8080).into(),
             cert_file: None,
             key_file: None,
+            trusted_proxies: Vec::new(),
         }
     }
 }
@@ -56,36 +80,491 @@ pub struct MsgConfig {
     /// The maximum message length in chars.
     pub max_length: usize,
 
-    /// The maximum amount of messages in `count_duration`.
-    pub max_messages: usize,
-
-    /// The duration in which the amount of messages cannot be greater.
+    /// The duration in which the same or a similar message cannot be sent twice.
     pub count_duration: WDuration,
+
+    /// The amount of recent messages which are kept, for example to be reported.
+    #[serde(default = "default_history_size")]
+    pub history_size: usize,
+
+    /// The maximum amount of members of a group conversation.
+    #[serde(default = "default_max_group_members")]
+    pub max_group_members: usize,
+
+    /// The maximum amount of group conversations a user can be a member of.
+    #[serde(default = "default_max_groups_per_user")]
+    pub max_groups_per_user: usize,
+
+    /// The rate limits for sending messages.
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+
+    /// The detection of repeated messages.
+    #[serde(default)]
+    pub spam: SpamConfig,
+}
+
+fn default_history_size() -> usize {
+    1000
+}
+
+fn default_max_group_members() -> usize {
+    20
+}
+
+fn default_max_groups_per_user() -> usize {
+    10
 }
 
 impl Default for MsgConfig {
     fn default() -> MsgConfig {
         MsgConfig {
             max_length: 100,
-            max_messages: 40,
             count_duration: Duration::from_secs(60).into(),
+            history_size: default_history_size(),
+            max_group_members: default_max_group_members(),
+            max_groups_per_user: default_max_groups_per_user(),
+            rate_limit: RateLimitConfig::default(),
+            spam: SpamConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SpamConfig {
+    /// The amount of recent messages of a user new messages are compared with.
+    pub history: usize,
+
+    /// The similarity from `0.0` to `1.0` at which two messages are treated as equal.
+    pub similarity: f64,
+
+    /// The minimum length of normalized messages which are compared by similarity
+    /// or checked for floods. Shorter messages are only rejected if they are repeated exactly.
+    pub min_length: usize,
+
+    /// The amount of different users sending the same message in `count_duration`
+    /// at which it is treated as a flood.
+    pub flood_users: usize,
+}
+
+impl Default for SpamConfig {
+    fn default() -> SpamConfig {
+        SpamConfig {
+            history: 5,
+            similarity: 0.8,
+            min_length: 4,
+            flood_users: 3,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RateLimitConfig {
+    /// The duration for which a user is treated as new.
+    pub new_account_duration: WDuration,
+
+    /// The limits for regular users.
+    pub default: ChannelLimits,
+
+    /// The limits for moderators.
+    pub moderator: ChannelLimits,
+
+    /// The limits for users who were first seen less than `new_account_duration` ago.
+    pub new_account: ChannelLimits,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> RateLimitConfig {
+        RateLimitConfig {
+            new_account_duration: Duration::from_secs(60 * 60 * 24).into(),
+            default: ChannelLimits {
+                public: BucketConfig::new(5, Duration::from_secs(2)),
+                private: BucketConfig::new(10, Duration::from_secs(1)),
+            },
+            moderator: ChannelLimits {
+                public: BucketConfig::new(20, Duration::from_millis(500)),
+                private: BucketConfig::new(20, Duration::from_millis(500)),
+            },
+            new_account: ChannelLimits {
+                public: BucketConfig::new(3, Duration::from_secs(5)),
+                private: BucketConfig::new(5, Duration::from_secs(3)),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ChannelLimits {
+    /// The limit for messages sent to everyone.
+    pub public: BucketConfig,
+
+    /// The limit for private messages.
+    pub private: BucketConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct BucketConfig {
+    /// The amount of messages which can be sent in a burst.
+    pub burst: u32,
+
+    /// The time after which another message can be sent.
+    pub refill: WDuration,
+}
+
+impl BucketConfig {
+    pub fn new(burst: u32, refill: Duration) -> BucketConfig {
+        BucketConfig {
+            burst,
+            refill: refill.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MojangConfig {
+    /// Whether users can log in with `LoginMojang`.
+    #[serde(default = "default_true")]
+    pub allow_mojang: bool,
+
+    /// Whether users can log in with `LoginMinecraft`.
+    #[serde(default = "default_true")]
+    pub allow_minecraft_services: bool,
+
+    /// The `hasJoined` endpoint of the session server.
+    /// It can point to a proxy, an authlib-injector compatible server
+    /// or the mock session server started with `axochat mock-session-server`.
+    #[serde(default = "default_session_server")]
+    pub session_server: String,
+
+    /// The Minecraft services endpoint returning the profile of an access token,
+    /// used by `LoginMinecraft`.
+    #[serde(default = "default_profile_endpoint")]
+    pub profile_endpoint: String,
+
+    /// The time after which a request to the session server is aborted.
+    #[serde(default = "default_session_timeout")]
+    pub timeout: WDuration,
+
+    /// How often a request is retried if the session server could not be reached
+    /// or responded with a server error.
+    #[serde(default = "default_session_retries")]
+    pub retries: u32,
+
+    /// The time to wait before the first retry. It is doubled for every further retry.
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: WDuration,
+
+    /// The maximum amount of requests sent to the session server at once.
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+
+    /// The maximum amount of requests waiting to be sent.
+    /// If the queue is full, logins fail with `AuthUnavailable`.
+    #[serde(default = "default_max_queued_requests")]
+    pub max_queued_requests: usize,
+
+    /// The time for which successful profile lookups of `LoginMinecraft` are cached.
+    /// Lookups of `LoginMojang` can't be cached, as every connection joins with another
+    /// server id.
+    #[serde(default = "default_cache_duration")]
+    pub cache_duration: WDuration,
+
+    /// The DER encoded public key of the session server,
+    /// for Mojang's session server the `yggdrasil_session_pubkey.der` shipped with authlib.
+    /// Both `SubjectPublicKeyInfo` and `RSAPublicKey` keys are accepted.
+    /// If it is set, the properties of every profile returned by the session server
+    /// need to be signed with it, otherwise signatures are not verified.
+    pub signature_key: Option<PathBuf>,
+}
+
+fn default_session_server() -> String {
+    String::from("https://sessionserver.mojang.com/session/minecraft/hasJoined")
+}
+
+fn default_profile_endpoint() -> String {
+    String::from("https://api.minecraftservices.com/minecraft/profile")
+}
+
+fn default_session_timeout() -> WDuration {
+    Duration::from_secs(5).into()
+}
+
+fn default_session_retries() -> u32 {
+    2
+}
+
+fn default_retry_delay() -> WDuration {
+    Duration::from_millis(250).into()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
+fn default_max_queued_requests() -> usize {
+    256
+}
+
+fn default_cache_duration() -> WDuration {
+    Duration::from_secs(30).into()
+}
+
+impl Default for MojangConfig {
+    fn default() -> MojangConfig {
+        MojangConfig {
+            allow_mojang: true,
+            allow_minecraft_services: true,
+            session_server: default_session_server(),
+            profile_endpoint: default_profile_endpoint(),
+            timeout: default_session_timeout(),
+            retries: default_session_retries(),
+            retry_delay: default_retry_delay(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            max_queued_requests: default_max_queued_requests(),
+            cache_duration: default_cache_duration(),
+            signature_key: None,
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AuthConfig {
-    /// The file containing the key of the JWT
-    pub key_file: PathBuf,
+    /// The file containing the key of the JWT.
+    /// It is only used if `keys` is not set.
+    pub key_file: Option<PathBuf>,
 
-    /// The JWT algorithm
+    /// The JWT algorithm of `key_file`
+    #[serde(default)]
     pub algorithm: Algorithm,
 
+    /// The file containing the signing key and verification keys of JWTs (TOML).
+    /// Tokens are signed with RS256 or ES256 if the signing key uses one of these algorithms,
+    /// so that other services can verify them using the public key.
+    pub keys: Option<PathBuf>,
+
+    /// The interval in which the key file is checked for changes.
+    #[serde(default = "default_key_reload_interval")]
+    pub key_reload_interval: WDuration,
+
     /// The time for which a JWT is valid
     pub valid_time: WDuration,
 
+    /// The time for which a refresh token is valid
+    #[serde(default = "default_refresh_valid_time")]
+    pub refresh_valid_time: WDuration,
+
+    /// The file containing the revoked tokens (JSON)
+    #[serde(default = "default_revocations")]
+    pub revocations: PathBuf,
+
+    /// The `iss` claim of issued JWTs.
+    /// If set, only tokens with this issuer are accepted.
+    pub issuer: Option<String>,
+
+    /// The `aud` claim of issued JWTs.
+    /// If set, only tokens for this audience are accepted.
+    pub audience: Option<String>,
+
     /// Whether users can be anonymous
     pub allow_anonymous: bool,
+
+    /// The permissions embedded into JWTs, so that other services can authorize users
+    /// without asking the chat server.
+    #[serde(default)]
+    pub permissions: PermissionConfig,
+}
+
+/// The permissions of every role.
+/// A role also has the permissions of all roles below it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PermissionConfig {
+    #[serde(default)]
+    pub user: Vec<String>,
+    #[serde(default)]
+    pub moderator: Vec<String>,
+    #[serde(default)]
+    pub admin: Vec<String>,
+}
+
+impl PermissionConfig {
+    pub fn of(&self, role: Role) -> Vec<String> {
+        let mut permissions = self.user.clone();
+        if role >= Role::Moderator {
+            permissions.extend(self.moderator.iter().cloned());
+        }
+        if role >= Role::Admin {
+            permissions.extend(self.admin.iter().cloned());
+        }
+        permissions.sort();
+        permissions.dedup();
+        permissions
+    }
+}
+
+fn default_key_reload_interval() -> WDuration {
+    Duration::from_secs(60).into()
+}
+
+fn default_refresh_valid_time() -> WDuration {
+    Duration::from_secs(60 * 60 * 24 * 30).into()
+}
+
+fn default_revocations() -> PathBuf {
+    PathBuf::from("./revoked_tokens.json")
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilterConfig {
+    /// The file containing the filter rules.
+    pub rules: PathBuf,
+
+    /// The interval in which the rule file is checked for changes.
+    pub reload_interval: WDuration,
+}
+
+impl Default for FilterConfig {
+    fn default() -> FilterConfig {
+        FilterConfig {
+            rules: PathBuf::from("./filter.toml"),
+            reload_interval: Duration::from_secs(10).into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkConfig {
+    /// Whether links are checked against `allowed` or `denied`.
+    pub policy: LinkPolicy,
+
+    /// The domains users may link to if the policy is `allow_list`.
+    /// Subdomains are allowed as well.
+    pub allowed: Vec<String>,
+
+    /// The domains users may not link to if the policy is `deny_list`.
+    /// Subdomains are denied as well.
+    pub denied: Vec<String>,
+
+    /// Whether IPv4 and IPv6 addresses are rejected.
+    pub block_addresses: bool,
+
+    /// Whether Discord invites are rejected, regardless of the policy.
+    pub block_invites: bool,
+
+    /// The lowercase top level domains of domains which are treated as links
+    /// even without a scheme or `www.`, like `example.com`.
+    #[serde(default = "default_tlds")]
+    pub tlds: Vec<String>,
+}
+
+fn default_tlds() -> Vec<String> {
+    // common words like `is` or `to` are left out, as they are often used in normal messages
+    [
+        "com", "net", "org", "info", "biz", "xyz", "top", "online", "site", "club", "shop",
+        "store", "app", "dev", "io", "gg", "co", "cc", "tv", "ly", "tk", "ml", "ga", "cf", "gq",
+        "pw", "ru", "su", "de", "uk", "fr", "nl", "pl", "eu", "ca", "au", "br", "cn", "jp", "kr",
+        "cz", "ch", "es", "se", "dk", "fi", "ua", "tr", "ro", "hu",
+    ]
+    .iter()
+    .map(|tld| String::from(*tld))
+    .collect()
+}
+
+impl Default for LinkConfig {
+    fn default() -> LinkConfig {
+        LinkConfig {
+            policy: LinkPolicy::DenyList,
+            allowed: vec![String::from("liquidbounce.net"), String::from("github.com")],
+            denied: Vec::new(),
+            block_addresses: true,
+            block_invites: true,
+            tlds: default_tlds(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkPolicy {
+    /// Only links to allowed domains are accepted.
+    AllowList,
+    /// Links to all domains except denied ones are accepted.
+    DenyList,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResumeConfig {
+    /// The time in which a disconnected session may be resumed by a new connection.
+    /// Sessions can't be resumed if this is zero.
+    pub grace_period: WDuration,
+
+    /// The maximum amount of messages buffered for a disconnected session.
+    /// If more messages are received, the oldest ones are dropped.
+    pub max_buffered_messages: usize,
+
+    /// The maximum amount of disconnected sessions of a single user.
+    /// If more connections of the user are closed, their oldest sessions can no longer be resumed.
+    #[serde(default = "default_max_suspended_per_user")]
+    pub max_suspended_per_user: usize,
+}
+
+fn default_max_suspended_per_user() -> usize {
+    2
+}
+
+impl Default for ResumeConfig {
+    fn default() -> ResumeConfig {
+        ResumeConfig {
+            grace_period: Duration::from_secs(30).into(),
+            max_buffered_messages: 100,
+            max_suspended_per_user: default_max_suspended_per_user(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DirectoryConfig {
+    /// The file containing the time each user was first seen and the names they used
+    /// (line separated).
+    pub first_seen: PathBuf,
+
+    /// The file containing the preferences of the users (JSON).
+    #[serde(default = "default_preferences")]
+    pub preferences: PathBuf,
+
+    /// The file containing the friends and friend requests of the users (JSON).
+    #[serde(default = "default_friends")]
+    pub friends: PathBuf,
+
+    /// The file containing the group conversations (JSON).
+    #[serde(default = "default_groups")]
+    pub groups: PathBuf,
+}
+
+fn default_preferences() -> PathBuf {
+    PathBuf::from("./preferences.json")
+}
+
+fn default_friends() -> PathBuf {
+    PathBuf::from("./friends.json")
+}
+
+fn default_groups() -> PathBuf {
+    PathBuf::from("./groups.json")
+}
+
+impl Default for DirectoryConfig {
+    fn default() -> DirectoryConfig {
+        DirectoryConfig {
+            first_seen: PathBuf::from("./first_seen.txt"),
+            preferences: default_preferences(),
+            friends: default_friends(),
+            groups: default_groups(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -95,6 +574,48 @@ pub struct ModConfig {
 
     /// The file containing the banned users (line separated).
     pub banned: PathBuf,
+
+    /// The file containing the reports of users (JSON).
+    #[serde(default = "default_reports")]
+    pub reports: PathBuf,
+
+    /// The file containing the admins (line separated).
+    /// Admins are moderators which may also read the audit log.
+    #[serde(default = "default_admins")]
+    pub admins: PathBuf,
+
+    /// The file every moderation action is logged to (JSON lines).
+    #[serde(default = "default_audit_log")]
+    pub audit_log: PathBuf,
+
+    /// The file containing the shadow banned users (line separated).
+    /// Messages of shadow banned users are only shown to themselves and moderators.
+    #[serde(default = "default_shadow_banned")]
+    pub shadow_banned: PathBuf,
+
+    /// The file containing the bans of IP ranges and client identifiers (JSON).
+    #[serde(default = "default_target_bans")]
+    pub target_bans: PathBuf,
+}
+
+fn default_reports() -> PathBuf {
+    PathBuf::from("./reports.json")
+}
+
+fn default_admins() -> PathBuf {
+    PathBuf::from("./admins.txt")
+}
+
+fn default_audit_log() -> PathBuf {
+    PathBuf::from("./audit.jsonl")
+}
+
+fn default_shadow_banned() -> PathBuf {
+    PathBuf::from("./shadow_banned.txt")
+}
+
+fn default_target_bans() -> PathBuf {
+    PathBuf::from("./target_bans.json")
 }
 
 impl Default for ModConfig {
@@ -102,10 +623,97 @@ impl Default for ModConfig {
         ModConfig {
             moderators: PathBuf::from("./moderators.txt"),
             banned: PathBuf::from("./banned.txt"),
+            reports: default_reports(),
+            admins: default_admins(),
+            audit_log: default_audit_log(),
+            shadow_banned: default_shadow_banned(),
+            target_bans: default_target_bans(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LimitConfig {
+    /// The maximum amount of open connections.
+    pub max_connections: usize,
+
+    /// The maximum amount of open connections from a single IP address.
+    pub max_connections_per_ip: usize,
+
+    /// The maximum amount of logged in connections of a single user.
+    pub max_connections_per_user: usize,
+
+    /// What happens if a user with `max_connections_per_user` connections logs in again.
+    #[serde(default)]
+    pub session_policy: SessionPolicy,
+
+    /// The maximum amount of packets a connection which is not logged in
+    /// may send in `unauthenticated_duration`.
+    pub max_unauthenticated_packets: usize,
+
+    /// The duration in which the amount of unauthenticated packets cannot be greater.
+    pub unauthenticated_duration: WDuration,
+
+    /// The maximum amount of friend requests a user may have sent which were not answered yet.
+    #[serde(default = "default_max_pending_friend_requests")]
+    pub max_pending_friend_requests: usize,
+
+    /// The limit for reports of a single user.
+    #[serde(default = "default_report_limit")]
+    pub reports: BucketConfig,
+
+    /// The limit for friend requests of a single user.
+    #[serde(default = "default_friend_request_limit")]
+    pub friend_requests: BucketConfig,
+
+    /// The limit for group conversations created by a single user.
+    #[serde(default = "default_group_limit")]
+    pub groups: BucketConfig,
+}
+
+fn default_report_limit() -> BucketConfig {
+    BucketConfig::new(3, Duration::from_secs(60))
+}
+
+fn default_group_limit() -> BucketConfig {
+    BucketConfig::new(3, Duration::from_secs(60))
+}
+
+fn default_friend_request_limit() -> BucketConfig {
+    BucketConfig::new(5, Duration::from_secs(60))
+}
+
+fn default_max_pending_friend_requests() -> usize {
+    50
+}
+
+impl Default for LimitConfig {
+    fn default() -> LimitConfig {
+        LimitConfig {
+            max_connections: 10_000,
+            max_connections_per_ip: 8,
+            max_connections_per_user: 4,
+            session_policy: SessionPolicy::default(),
+            max_unauthenticated_packets: 10,
+            unauthenticated_duration: Duration::from_secs(10).into(),
+            reports: default_report_limit(),
+            groups: default_group_limit(),
+            friend_requests: default_friend_request_limit(),
+            max_pending_friend_requests: default_max_pending_friend_requests(),
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPolicy {
+    /// The new login is rejected with `TooManyConnections`.
+    #[default]
+    Reject,
+    /// The oldest connection is logged out and closed.
+    EvictOldest,
+}
+
 /// Reads the configuration file at `$CONFIG_PATH` or creates one if none was found.
 pub fn read_config() -> Result<Config> {
     let path = env::var("CONFIG_PATH").unwrap_or_else(|_| String::from("./axochat.toml"));
@@ -182,3 +790,16 @@ impl Serialize for WDuration {
         serializer.serialize_str(&duration.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_round_trip() {
+        // scalar fields have to come before tables, otherwise serializing fails
+        let output = toml::to_string_pretty(&Config::default()).unwrap();
+        let cfg: Config = toml::from_str(&output).unwrap();
+        assert_eq!(toml::to_string_pretty(&cfg).unwrap(), output);
+    }
+}
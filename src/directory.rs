@@ -0,0 +1,181 @@
+use crate::config::DirectoryConfig;
+use crate::error::*;
+use crate::time::unix_secs;
+use log::*;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+/// A name a user was seen with.
+#[derive(Debug, Clone, Serialize)]
+pub struct KnownName {
+    pub name: String,
+    /// The time the user was first seen with this name in seconds since the unix epoch.
+    pub time: u64,
+}
+
+struct Seen {
+    first_seen: SystemTime,
+    /// The names of the user, oldest first.
+    names: Vec<KnownName>,
+}
+
+/// A line of the directory file.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    user: Uuid,
+    /// The time in seconds since the unix epoch.
+    time: u64,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Persistent information about the users this server has seen.
+///
+/// Every line of the file is a JSON object containing the uuid of a user,
+/// a time in seconds since the unix epoch and optionally the name of the user at that time.
+/// A new line is appended every time a user is seen with a new name.
+///
+/// Lines in the old format, `<uuid> <time> [name]`, are still read.
+pub struct Directory {
+    config: DirectoryConfig,
+    users: HashMap<Uuid, Seen>,
+}
+
+impl Directory {
+    pub fn new(config: DirectoryConfig) -> Result<Directory> {
+        let users = read_users(&config.first_seen)?;
+        Ok(Directory { config, users })
+    }
+
+    /// Registers that `user` logged in with `name`.
+    pub fn see(&mut self, user: &Uuid, name: &str) -> Result<()> {
+        let known = self
+            .users
+            .get(user)
+            .and_then(|seen| seen.names.last())
+            .is_some_and(|known| known.name == name);
+        if known {
+            return Ok(());
+        }
+
+        let now = SystemTime::now();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.config.first_seen)?;
+        let record = Record {
+            user: *user,
+            time: unix_secs(now),
+            name: Some(name.to_owned()),
+        };
+        // names are escaped by JSON, so they can't add lines
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+
+        add_name(&mut self.users, *user, now, Some(name));
+        Ok(())
+    }
+
+    pub fn first_seen(&self, user: &Uuid) -> Option<SystemTime> {
+        self.users.get(user).map(|seen| seen.first_seen)
+    }
+
+    /// Returns the names `user` was seen with, oldest first.
+    pub fn names(&self, user: &Uuid) -> &[KnownName] {
+        self.users
+            .get(user)
+            .map_or(&[], |seen| seen.names.as_slice())
+    }
+
+    /// Returns the user who most recently used `name`, ignoring case.
+    pub fn find_by_name(&self, name: &str) -> Option<Uuid> {
+        self.users
+            .iter()
+            .filter_map(|(user, seen)| {
+                let last = seen.names.last()?;
+                if last.name.eq_ignore_ascii_case(name) {
+                    Some((last.time, *user))
+                } else {
+                    None
+                }
+            })
+            .max_by_key(|(time, _)| *time)
+            .map(|(_, user)| user)
+    }
+
+    /// Returns whether `user` was first seen less than `duration` ago.
+    /// Users which were never seen are new.
+    pub fn is_new(&self, user: &Uuid, duration: Duration) -> bool {
+        match self.first_seen(user) {
+            Some(time) => time.elapsed().map(|age| age < duration).unwrap_or(true),
+            None => true,
+        }
+    }
+}
+
+fn add_name(users: &mut HashMap<Uuid, Seen>, user: Uuid, time: SystemTime, name: Option<&str>) {
+    let seen = users.entry(user).or_insert_with(|| Seen {
+        first_seen: time,
+        names: Vec::new(),
+    });
+    if let Some(name) = name {
+        if seen.names.last().is_none_or(|known| known.name != name) {
+            seen.names.push(KnownName {
+                name: name.to_owned(),
+                time: unix_secs(time),
+            });
+        }
+    }
+}
+
+fn read_users(path: &Path) -> Result<HashMap<Uuid, Seen>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => {
+            File::create(path)?;
+            return Ok(HashMap::new());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let reader = BufReader::new(file);
+    let mut users = HashMap::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = parse_record(&line).and_then(|record| {
+            let time = SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(record.time))?;
+            Some((record, time))
+        });
+        match record {
+            Some((record, time)) => add_name(&mut users, record.user, time, record.name.as_deref()),
+            None => warn!(
+                "Skipping invalid line {} of {}.",
+                number + 1,
+                path.display()
+            ),
+        }
+    }
+    Ok(users)
+}
+
+fn parse_record(line: &str) -> Option<Record> {
+    if line.starts_with('{') {
+        return serde_json::from_str(line).ok();
+    }
+
+    let mut parts = line.splitn(3, ' ');
+    Some(Record {
+        user: parts.next()?.parse().ok()?,
+        time: parts.next()?.parse().ok()?,
+        name: parts.next().map(String::from),
+    })
+}
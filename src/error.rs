@@ -1,7 +1,7 @@
 use derive_more::From;
 use serde::Serialize;
 use snafu::Snafu;
-use std::{error, fmt, io};
+use std::{error, fmt, io, time::Duration};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -28,6 +28,10 @@ pub enum Error {
     JWT { source: jsonwebtoken::errors::Error },
     #[snafu(display("UUID parsing: {}", source))]
     Uuid { source: uuid::parser::ParseError },
+    #[snafu(display("integer parsing: {}", source))]
+    ParseInt { source: std::num::ParseIntError },
+    #[snafu(display("regex: {}", source))]
+    Regex { source: regex::Error },
     #[snafu(display("axochat: {}", source))]
     AxoChat { source: ClientError },
 }
@@ -43,15 +47,48 @@ pub enum ClientError {
     NotPermitted,
     NotBanned,
     Banned,
-    RateLimited,
+    /// `retry_after` is the time in milliseconds after which the action may be retried.
+    RateLimited {
+        retry_after: u64,
+    },
     PrivateMessageNotAccepted,
+    Spam,
+    /// `reason` is the reason given by the rule which blocked the message.
+    MessageFiltered {
+        reason: Option<String>,
+    },
+    LinkNotAllowed,
+    Lockdown,
+    AccountTooNew,
+    InvalidChatMode,
     EmptyMessage,
     MessageTooLong,
     InvalidCharacter(char),
     InvalidId,
+    UnknownUser,
+    AlreadyReported,
+    AlreadyFriends,
+    NotFriends,
+    NoFriendRequest,
+    FriendRequestNotAccepted,
+    TooManyFriendRequests,
+    GroupFull,
+    AlreadyMember,
+    TooManyGroups,
+    TooManyConnections,
+    AuthUnavailable,
+    SessionTerminated,
     Internal,
 }
 
+impl ClientError {
+    pub fn rate_limited(retry_after: Duration) -> ClientError {
+        ClientError::RateLimited {
+            retry_after: retry_after.as_millis() as u64,
+        }
+    }
+}
+
 impl error::Error for ClientError {}
 
 impl fmt::Display for ClientError {
@@ -67,8 +104,19 @@ impl fmt::Display for ClientError {
             NotPermitted => write!(f, "not permitted"),
             NotBanned => write!(f, "not banned"),
             Banned => write!(f, "banned"),
-            RateLimited => write!(f, "rate limited"),
+            RateLimited { retry_after } => {
+                write!(f, "rate limited; retry after {}ms", retry_after)
+            }
             PrivateMessageNotAccepted => write!(f, "private message not accepted"),
+            Spam => write!(f, "message was detected as spam"),
+            MessageFiltered { reason: None } => write!(f, "message was blocked by the filter"),
+            MessageFiltered {
+                reason: Some(reason),
+            } => write!(f, "message was blocked by the filter: {}", reason),
+            LinkNotAllowed => write!(f, "message contained a link which is not allowed"),
+            Lockdown => write!(f, "only moderators may write during lockdown"),
+            AccountTooNew => write!(f, "account is too new to write currently"),
+            InvalidChatMode => write!(f, "invalid chat mode"),
             EmptyMessage => write!(f, "empty message"),
             MessageTooLong => write!(f, "message was too long"),
             InvalidCharacter(ch) => write!(
@@ -77,6 +125,19 @@ impl fmt::Display for ClientError {
                 ch.escape_default()
             ),
             InvalidId => write!(f, "invalid id"),
+            UnknownUser => write!(f, "unknown user"),
+            AlreadyReported => write!(f, "already reported"),
+            AlreadyFriends => write!(f, "already friends"),
+            NotFriends => write!(f, "not friends"),
+            NoFriendRequest => write!(f, "no friend request"),
+            FriendRequestNotAccepted => write!(f, "friend request not accepted"),
+            TooManyFriendRequests => write!(f, "too many pending friend requests"),
+            GroupFull => write!(f, "group is full"),
+            AlreadyMember => write!(f, "already a member of the group"),
+            TooManyGroups => write!(f, "member of too many groups"),
+            TooManyConnections => write!(f, "too many connections"),
+            AuthUnavailable => write!(f, "authentication server unavailable"),
+            SessionTerminated => write!(f, "session was terminated"),
             Internal => write!(f, "internal error"),
         }
     }
@@ -0,0 +1,201 @@
+use crate::config::FilterConfig;
+use crate::error::*;
+use crate::normalize::Normalized;
+use log::*;
+
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    fs::{self, File},
+    io,
+    ops::Range,
+    time::SystemTime,
+};
+
+/// What happens to a message matching a rule.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Replace the matched text with asterisks.
+    Censor,
+    /// Reject the message.
+    Block,
+    /// Deliver the message, but notify the moderators.
+    Flag,
+}
+
+/// The file containing the filter rules.
+///
+/// ```toml
+/// [[rule]]
+/// words = ["badword"]
+/// action = "censor"
+///
+/// [[rule]]
+/// pattern = "free\\s*v-?bucks"
+/// action = "block"
+/// reason = "scam"
+/// ```
+#[derive(Deserialize)]
+struct RuleFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<RuleConfig>,
+}
+
+#[derive(Deserialize)]
+struct RuleConfig {
+    /// Words which are matched as a whole, ignoring repeated characters.
+    #[serde(default)]
+    words: Vec<String>,
+    /// A regular expression.
+    pattern: Option<String>,
+    action: Action,
+    /// The reason sent to the user if the rule blocks their message.
+    reason: Option<String>,
+}
+
+struct Rule {
+    regex: Regex,
+    action: Action,
+    reason: Option<String>,
+}
+
+impl Rule {
+    fn new(cfg: RuleConfig) -> Result<Rule> {
+        let mut alternatives: Vec<String> = cfg
+            .words
+            .iter()
+            .map(|word| {
+                let word = Normalized::words(word);
+                let chars: String = word
+                    .as_str()
+                    .chars()
+                    .map(|ch| format!("{}+", regex::escape(&ch.to_string())))
+                    .collect();
+                format!(r"\b{}\b", chars)
+            })
+            .collect();
+        alternatives.extend(cfg.pattern);
+
+        Ok(Rule {
+            regex: Regex::new(&alternatives.join("|"))?,
+            action: cfg.action,
+            reason: cfg.reason,
+        })
+    }
+}
+
+/// The result of filtering a message.
+pub struct Filtered {
+    /// The message with censored parts replaced with asterisks.
+    pub content: String,
+    /// The parts of the message which should be reported to moderators.
+    pub flagged: Vec<String>,
+}
+
+/// Filters messages using rules read from a file.
+///
+/// The rules are matched against the message normalized with [`Normalized::words`].
+pub struct Filter {
+    config: FilterConfig,
+    rules: Vec<Rule>,
+    modified: Option<SystemTime>,
+}
+
+impl Filter {
+    pub fn new(config: FilterConfig) -> Result<Filter> {
+        let mut filter = Filter {
+            config,
+            rules: Vec::new(),
+            modified: None,
+        };
+        filter.load()?;
+        Ok(filter)
+    }
+
+    /// Reloads the rules if the rule file was modified.
+    /// If the new rules are invalid, the old ones stay in use.
+    pub fn reload_if_modified(&mut self) {
+        let modified = fs::metadata(&self.config.rules)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified != self.modified {
+            match self.load() {
+                Ok(()) => info!("Reloaded {} filter rules.", self.rules.len()),
+                Err(err) => {
+                    warn!("Could not reload filter rules: {}", err);
+                    self.modified = modified;
+                }
+            }
+        }
+    }
+
+    fn load(&mut self) -> Result<()> {
+        let path = &self.config.rules;
+        let input = match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                File::create(path)?;
+                String::new()
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let modified = fs::metadata(path)?.modified().ok();
+
+        let file: RuleFile = toml::from_str(&input)?;
+        self.rules = file
+            .rules
+            .into_iter()
+            .filter(|rule| !rule.words.is_empty() || rule.pattern.is_some())
+            .map(Rule::new)
+            .collect::<Result<_>>()?;
+        self.modified = modified;
+
+        Ok(())
+    }
+
+    /// Applies the rules to `msg`.
+    /// If the message should be blocked, `ClientError::MessageFiltered` is returned.
+    pub fn apply(&self, msg: &str) -> Result<Filtered> {
+        let normalized = Normalized::words(msg);
+        let mut censored: Vec<Range<usize>> = Vec::new();
+        let mut flagged = Vec::new();
+
+        for rule in &self.rules {
+            // rules matching the empty string would match every message
+            for found in rule
+                .regex
+                .find_iter(normalized.as_str())
+                .filter(|found| !found.as_str().is_empty())
+            {
+                let range = normalized.original_range(found.start()..found.end());
+                match rule.action {
+                    Action::Censor => censored.push(range),
+                    Action::Block => {
+                        return Err(ClientError::MessageFiltered {
+                            reason: rule.reason.clone(),
+                        }
+                        .into())
+                    }
+                    Action::Flag => flagged.push(msg[range].to_owned()),
+                }
+            }
+        }
+
+        let content = if censored.is_empty() {
+            msg.to_owned()
+        } else {
+            msg.char_indices()
+                .map(|(index, ch)| {
+                    if !ch.is_whitespace() && censored.iter().any(|range| range.contains(&index)) {
+                        '*'
+                    } else {
+                        ch
+                    }
+                })
+                .collect()
+        };
+
+        Ok(Filtered { content, flagged })
+    }
+}
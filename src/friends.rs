@@ -0,0 +1,153 @@
+use crate::error::*;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, io,
+    path::PathBuf,
+};
+use uuid::Uuid;
+
+#[derive(Default, Serialize, Deserialize)]
+struct FriendData {
+    /// The friends of every user. Friendships are always stored in both directions.
+    #[serde(default)]
+    friends: HashMap<Uuid, BTreeSet<Uuid>>,
+    /// The users who sent a friend request, by the user they sent it to.
+    #[serde(default)]
+    requests: HashMap<Uuid, BTreeSet<Uuid>>,
+}
+
+/// The persisted friendships and friend requests, stored as JSON.
+///
+/// Changes are only written by [`Friends::save_if_changed`].
+pub struct Friends {
+    path: PathBuf,
+    data: FriendData,
+    changed: bool,
+}
+
+impl Friends {
+    pub fn new(path: PathBuf) -> Result<Friends> {
+        let data = match fs::read(&path) {
+            Ok(input) => serde_json::from_slice(&input)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => FriendData::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Friends {
+            path,
+            data,
+            changed: false,
+        })
+    }
+
+    /// Sends a friend request from `from` to `to`.
+    /// If `to` already sent a request to `from`, they become friends and `true` is returned.
+    /// `from` may only have `max_pending` requests which were not answered yet.
+    pub fn request(&mut self, from: Uuid, to: Uuid, max_pending: usize) -> Result<bool> {
+        if from == to {
+            return Err(ClientError::NotPermitted.into());
+        }
+        if self.are_friends(&from, &to) {
+            return Err(ClientError::AlreadyFriends.into());
+        }
+
+        if self.has_request(&from, &to) {
+            self.accept(from, to)?;
+            Ok(true)
+        } else if self.has_request(&to, &from) {
+            Ok(false)
+        } else if self.outgoing(&from).count() >= max_pending {
+            Err(ClientError::TooManyFriendRequests.into())
+        } else {
+            self.data.requests.entry(to).or_default().insert(from);
+            self.changed = true;
+            Ok(false)
+        }
+    }
+
+    /// Accepts the friend request `from` sent to `user`.
+    pub fn accept(&mut self, user: Uuid, from: Uuid) -> Result<()> {
+        if !self.has_request(&user, &from) {
+            return Err(ClientError::NoFriendRequest.into());
+        }
+
+        remove(&mut self.data.requests, &user, &from);
+        self.data.friends.entry(user).or_default().insert(from);
+        self.data.friends.entry(from).or_default().insert(user);
+        self.changed = true;
+        Ok(())
+    }
+
+    /// Ends the friendship of `user` and `other`.
+    /// Pending friend requests between them are removed as well.
+    pub fn remove(&mut self, user: Uuid, other: Uuid) -> Result<()> {
+        let mut removed = remove(&mut self.data.friends, &user, &other);
+        removed |= remove(&mut self.data.friends, &other, &user);
+        removed |= remove(&mut self.data.requests, &user, &other);
+        removed |= remove(&mut self.data.requests, &other, &user);
+
+        if removed {
+            self.changed = true;
+            Ok(())
+        } else {
+            Err(ClientError::NotFriends.into())
+        }
+    }
+
+    pub fn are_friends(&self, user: &Uuid, other: &Uuid) -> bool {
+        self.data
+            .friends
+            .get(user)
+            .is_some_and(|friends| friends.contains(other))
+    }
+
+    pub fn friends<'a>(&'a self, user: &Uuid) -> impl Iterator<Item = &'a Uuid> {
+        self.data.friends.get(user).into_iter().flatten()
+    }
+
+    /// Returns the users who sent a friend request to `user`.
+    pub fn incoming<'a>(&'a self, user: &Uuid) -> impl Iterator<Item = &'a Uuid> {
+        self.data.requests.get(user).into_iter().flatten()
+    }
+
+    /// Returns the users `user` sent a friend request to.
+    pub fn outgoing<'a>(&'a self, user: &'a Uuid) -> impl Iterator<Item = &'a Uuid> {
+        self.data
+            .requests
+            .iter()
+            .filter(move |(_, senders)| senders.contains(user))
+            .map(|(receiver, _)| receiver)
+    }
+
+    /// Returns whether `from` sent a friend request to `user`.
+    fn has_request(&self, user: &Uuid, from: &Uuid) -> bool {
+        self.data
+            .requests
+            .get(user)
+            .is_some_and(|senders| senders.contains(from))
+    }
+
+    /// Writes the friends if they were changed since they were last written.
+    pub fn save_if_changed(&mut self) -> Result<()> {
+        if self.changed {
+            fs::write(&self.path, serde_json::to_vec_pretty(&self.data)?)?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+}
+
+/// Removes `value` from the set of `key`, and the set if it becomes empty.
+fn remove(map: &mut HashMap<Uuid, BTreeSet<Uuid>>, key: &Uuid, value: &Uuid) -> bool {
+    match map.get_mut(key) {
+        Some(set) => {
+            let removed = set.remove(value);
+            if set.is_empty() {
+                map.remove(key);
+            }
+            removed
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,158 @@
+use crate::chat::GroupId;
+use crate::error::*;
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fs, io, path::PathBuf};
+use uuid::Uuid;
+
+/// A group conversation of several users.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub id: GroupId,
+    pub name: String,
+    /// The user who created the group. Only they may remove other members.
+    pub owner: Uuid,
+    pub members: BTreeSet<Uuid>,
+}
+
+/// The persisted groups, stored as JSON.
+///
+/// Changes are only written by [`Groups::save_if_changed`].
+pub struct Groups {
+    path: PathBuf,
+    groups: Vec<Group>,
+    max_members: usize,
+    max_groups_per_user: usize,
+    changed: bool,
+}
+
+impl Groups {
+    pub fn new(path: PathBuf, max_members: usize, max_groups_per_user: usize) -> Result<Groups> {
+        let groups = match fs::read(&path) {
+            Ok(input) => serde_json::from_slice(&input)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Groups {
+            path,
+            groups,
+            max_members,
+            max_groups_per_user,
+            changed: false,
+        })
+    }
+
+    /// Creates a new group of `owner` and `members`.
+    pub fn create(&mut self, owner: Uuid, name: String, members: Vec<Uuid>) -> Result<&Group> {
+        let mut members: BTreeSet<Uuid> = members.into_iter().collect();
+        members.insert(owner);
+        if members.len() > self.max_members {
+            return Err(ClientError::GroupFull.into());
+        }
+        if members.iter().any(|member| !self.can_join(member)) {
+            return Err(ClientError::TooManyGroups.into());
+        }
+
+        let id = self
+            .groups
+            .iter()
+            .map(|group| group.id)
+            .max()
+            .map_or(GroupId::new(1), GroupId::next);
+        self.groups.push(Group {
+            id,
+            name,
+            owner,
+            members,
+        });
+        self.changed = true;
+
+        Ok(self.groups.last().unwrap())
+    }
+
+    /// Returns the group if `user` is a member of it.
+    pub fn get(&self, id: GroupId, user: &Uuid) -> Result<&Group> {
+        self.groups
+            .iter()
+            .find(|group| group.id == id && group.members.contains(user))
+            .ok_or_else(|| ClientError::InvalidId.into())
+    }
+
+    /// Returns the group regardless of its members.
+    pub fn get_any(&self, id: GroupId) -> Result<&Group> {
+        self.groups
+            .iter()
+            .find(|group| group.id == id)
+            .ok_or_else(|| ClientError::InvalidId.into())
+    }
+
+    /// Returns the groups `user` is a member of.
+    pub fn of_user<'a>(&'a self, user: &'a Uuid) -> impl Iterator<Item = &'a Group> {
+        self.groups
+            .iter()
+            .filter(move |group| group.members.contains(user))
+    }
+
+    /// Adds `user` to the group. Every member may add users.
+    pub fn add_member(&mut self, id: GroupId, actor: &Uuid, user: Uuid) -> Result<&Group> {
+        let max_members = self.max_members;
+        let can_join = self.can_join(&user);
+        let group = self.get_mut(id, actor)?;
+        if group.members.contains(&user) {
+            return Err(ClientError::AlreadyMember.into());
+        }
+        if group.members.len() >= max_members {
+            return Err(ClientError::GroupFull.into());
+        }
+        if !can_join {
+            return Err(ClientError::TooManyGroups.into());
+        }
+        group.members.insert(user);
+        self.changed = true;
+
+        self.get(id, actor)
+    }
+
+    /// Removes `user` from the group.
+    /// Users may always remove themselves, others may only be removed by the owner.
+    /// If the owner leaves, another member becomes the owner.
+    /// If the group becomes empty, it is deleted.
+    pub fn remove_member(&mut self, id: GroupId, actor: &Uuid, user: &Uuid) -> Result<()> {
+        let group = self.get_mut(id, actor)?;
+        if actor != user && *actor != group.owner {
+            return Err(ClientError::NotPermitted.into());
+        }
+        if !group.members.remove(user) {
+            return Err(ClientError::UnknownUser.into());
+        }
+
+        if group.members.is_empty() {
+            self.groups.retain(|group| group.id != id);
+        } else if group.owner == *user {
+            group.owner = *group.members.iter().next().unwrap();
+        }
+        self.changed = true;
+        Ok(())
+    }
+
+    /// Returns whether `user` may become a member of another group.
+    fn can_join(&self, user: &Uuid) -> bool {
+        self.of_user(user).count() < self.max_groups_per_user
+    }
+
+    fn get_mut(&mut self, id: GroupId, user: &Uuid) -> Result<&mut Group> {
+        self.groups
+            .iter_mut()
+            .find(|group| group.id == id && group.members.contains(user))
+            .ok_or_else(|| ClientError::InvalidId.into())
+    }
+
+    /// Writes the groups if they were changed since they were last written.
+    pub fn save_if_changed(&mut self) -> Result<()> {
+        if self.changed {
+            fs::write(&self.path, serde_json::to_vec_pretty(&self.groups)?)?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use actix_web::HttpRequest;
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    convert::TryFrom,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// Returns the IP address of the client which sent `req`.
+///
+/// The `X-Forwarded-For` header is only honoured if the peer is one of `trusted_proxies`.
+/// In that case the rightmost address which is not a trusted proxy is used.
+pub fn client_ip(req: &HttpRequest, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let mut ip = req.peer_addr()?.ip();
+    if !trusted_proxies.contains(&ip) {
+        return Some(ip);
+    }
+
+    let forwarded: Vec<&str> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    for addr in forwarded.into_iter().rev() {
+        match addr.trim().parse() {
+            Ok(addr) => {
+                ip = addr;
+                if !trusted_proxies.contains(&ip) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    Some(ip)
+}
+
+/// Maps IPv4-mapped IPv6 addresses like `::ffff:192.0.2.1` to their IPv4 address.
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// A range of IP addresses in CIDR notation, e.g. `192.0.2.0/24`.
+/// A single address is a range with the full prefix length.
+///
+/// The address is always the first address of the range,
+/// so `10.0.0.5/8` and `10.0.0.0/8` are the same range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, canonical_ip(*ip)) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                let shift = 32 - u32::from(self.prefix);
+                u32::from(addr).checked_shr(shift) == u32::from(ip).checked_shr(shift)
+            }
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                let shift = 128 - u32::from(self.prefix);
+                u128::from(addr).checked_shr(shift) == u128::from(ip).checked_shr(shift)
+            }
+            _ => false,
+        }
+    }
+
+    /// Clears the bits of the address which are not part of the prefix.
+    fn masked(self) -> IpRange {
+        let addr = match self.addr {
+            IpAddr::V4(addr) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+            }
+            IpAddr::V6(addr) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+            }
+        };
+        IpRange { addr, ..self }
+    }
+}
+
+impl From<IpAddr> for IpRange {
+    fn from(addr: IpAddr) -> IpRange {
+        let addr = canonical_ip(addr);
+        let prefix = if addr.is_ipv4() { 32 } else { 128 };
+        IpRange { addr, prefix }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = InvalidIpRange;
+
+    fn from_str(s: &str) -> Result<IpRange, InvalidIpRange> {
+        let mut parts = s.splitn(2, '/');
+        let addr: IpAddr = parts
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .map_err(|_| InvalidIpRange)?;
+        let mapped = addr != canonical_ip(addr);
+        let mut range = IpRange::from(addr);
+        if let Some(prefix) = parts.next() {
+            let mut prefix: u8 = prefix.parse().map_err(|_| InvalidIpRange)?;
+            if mapped {
+                // the prefix of `::ffff:192.0.2.0/120` includes the 96 bits before the IPv4 address
+                prefix = prefix.checked_sub(96).ok_or(InvalidIpRange)?;
+            }
+            if prefix > range.prefix {
+                return Err(InvalidIpRange);
+            }
+            range.prefix = prefix;
+        }
+        Ok(range.masked())
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = InvalidIpRange;
+
+    fn try_from(s: String) -> Result<IpRange, InvalidIpRange> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl Serialize for IpRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InvalidIpRange;
+
+impl fmt::Display for InvalidIpRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid IP range")
+    }
+}
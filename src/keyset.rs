@@ -0,0 +1,206 @@
+use crate::error::*;
+use log::*;
+
+use jsonwebtoken::{Algorithm, Header};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// The file containing the keys of JWTs.
+///
+/// ```toml
+/// # The key used to sign new tokens, which is also used to verify tokens.
+/// [signing]
+/// id = "2019-10"
+/// algorithm = "RS256"
+/// private_key = "./keys/2019-10.der"
+/// public_key = "./keys/2019-10.pub.der"
+///
+/// # Keys which are only used to verify tokens, e.g. previous signing keys.
+/// [[verification]]
+/// id = "2019-09"
+/// algorithm = "HS256"
+/// key = "./keys/2019-09.key"
+/// ```
+///
+/// RSA keys are DER encoded, the private key in PKCS#1 and the public key as `RSAPublicKey`.
+/// ECDSA private keys are DER encoded in PKCS#8, the public keys are uncompressed points.
+/// For HMAC algorithms the private key is the shared secret and no public key is needed.
+#[derive(Deserialize)]
+struct KeyFile {
+    signing: SigningKeyConfig,
+    #[serde(default)]
+    verification: Vec<VerificationKeyConfig>,
+}
+
+#[derive(Deserialize)]
+struct SigningKeyConfig {
+    id: String,
+    algorithm: Algorithm,
+    private_key: PathBuf,
+    public_key: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct VerificationKeyConfig {
+    id: String,
+    algorithm: Algorithm,
+    key: PathBuf,
+}
+
+/// A key used to verify tokens.
+struct Key {
+    algorithm: Algorithm,
+    key: Vec<u8>,
+}
+
+/// The key used to sign new tokens.
+struct SigningKey {
+    /// The id sent in the `kid` header.
+    /// Tokens signed with the key of `AuthConfig::key_file` do not have one.
+    id: Option<String>,
+    algorithm: Algorithm,
+    key: Vec<u8>,
+}
+
+/// The keys used to sign and verify JWTs.
+pub struct Keyset {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    signing: SigningKey,
+    /// The verification keys by their id, including the signing key.
+    keys: HashMap<String, Key>,
+}
+
+impl Keyset {
+    /// Creates a key set consisting of a single key, which is both used to sign and verify tokens.
+    pub fn single(algorithm: Algorithm, key_file: &Path) -> Result<Keyset> {
+        let key = fs::read(key_file)?;
+        let mut keys = HashMap::new();
+        keys.insert(
+            String::new(),
+            Key {
+                algorithm,
+                key: key.clone(),
+            },
+        );
+
+        Ok(Keyset {
+            path: None,
+            modified: None,
+            signing: SigningKey {
+                id: None,
+                algorithm,
+                key,
+            },
+            keys,
+        })
+    }
+
+    /// Reads a key set from a key file.
+    pub fn from_file(path: PathBuf) -> Result<Keyset> {
+        let modified = fs::metadata(&path)?.modified().ok();
+        let file: KeyFile = toml::from_str(&fs::read_to_string(&path)?)?;
+
+        let signing = file.signing;
+        let public_key = match (&signing.public_key, is_hmac(signing.algorithm)) {
+            (Some(public_key), _) => fs::read(public_key)?,
+            (None, true) => fs::read(&signing.private_key)?,
+            (None, false) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the signing key needs a public key",
+                )
+                .into());
+            }
+        };
+
+        let mut keys = HashMap::new();
+        for verification in file.verification {
+            keys.insert(
+                verification.id,
+                Key {
+                    algorithm: verification.algorithm,
+                    key: fs::read(&verification.key)?,
+                },
+            );
+        }
+        keys.insert(
+            signing.id.clone(),
+            Key {
+                algorithm: signing.algorithm,
+                key: public_key,
+            },
+        );
+
+        Ok(Keyset {
+            path: Some(path),
+            modified,
+            signing: SigningKey {
+                key: fs::read(&signing.private_key)?,
+                id: Some(signing.id),
+                algorithm: signing.algorithm,
+            },
+            keys,
+        })
+    }
+
+    /// Reloads the keys if the key file was modified.
+    /// If the new keys are invalid, the old ones stay in use.
+    pub fn reload_if_modified(&mut self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified != self.modified {
+            match Keyset::from_file(path.clone()) {
+                Ok(keyset) => {
+                    info!("Reloaded {} JWT keys.", keyset.keys.len());
+                    *self = keyset;
+                }
+                Err(err) => {
+                    warn!("Could not reload JWT keys: {}", err);
+                    self.modified = modified;
+                }
+            }
+        }
+    }
+
+    /// Returns the header and key used to sign a new token.
+    pub fn signing_key(&self) -> (Header, &[u8]) {
+        let mut header = Header::new(self.signing.algorithm);
+        header.kid = self.signing.id.clone();
+        (header, &self.signing.key)
+    }
+
+    /// Returns the keys a token with `header` could be signed with.
+    ///
+    /// If the token has a `kid` header, only the key with this id is returned.
+    /// Tokens without one, e.g. issued before a key set was used,
+    /// are checked against every key with the algorithm of the token.
+    pub fn verification_keys<'a>(
+        &'a self,
+        header: &'a Header,
+    ) -> impl Iterator<Item = (Algorithm, &'a [u8])> + 'a {
+        self.keys
+            .iter()
+            .filter(move |(id, key)| {
+                key.algorithm == header.alg && header.kid.as_ref().is_none_or(|kid| kid == *id)
+            })
+            .map(|(_, key)| (key.algorithm, key.key.as_slice()))
+    }
+}
+
+fn is_hmac(algorithm: Algorithm) -> bool {
+    matches!(
+        algorithm,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    )
+}
@@ -0,0 +1,150 @@
+use crate::config::BucketConfig;
+use crate::error::{ClientError, Error};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Limits the amount of packets in a sliding window.
+pub struct PacketLimiter {
+    buf: VecDeque<Instant>,
+    max_packets: usize,
+    duration: Duration,
+}
+
+impl PacketLimiter {
+    pub fn new(max_packets: usize, duration: Duration) -> PacketLimiter {
+        PacketLimiter {
+            buf: VecDeque::with_capacity(max_packets),
+            max_packets,
+            duration,
+        }
+    }
+
+    /// Checks if a new packet in this instant would be rate limited.
+    /// If it would be, the time after which a packet will be accepted again is returned.
+    /// If not, then it registers the new packet instant.
+    pub fn check_new_packet(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        while let Some(time) = self.buf.front() {
+            if now.duration_since(*time) < self.duration {
+                break;
+            }
+            self.buf.pop_front();
+        }
+
+        if self.buf.len() < self.max_packets {
+            self.buf.push_back(now);
+            Ok(())
+        } else {
+            let oldest = self.buf.front().copied().unwrap_or(now);
+            Err(self.duration - now.duration_since(oldest))
+        }
+    }
+}
+
+/// A token bucket which holds up to `burst` tokens and regains one token every `refill`.
+///
+/// The configuration is passed on every call,
+/// so that the policy can change while the bucket is in use.
+pub struct TokenBucket {
+    tokens: u32,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a bucket which is completely filled.
+    pub fn new() -> TokenBucket {
+        TokenBucket {
+            // the amount is capped to `burst` on first use
+            tokens: u32::MAX,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token from the bucket.
+    /// If the bucket is empty, the time until the next token is available is returned.
+    pub fn take(&mut self, cfg: BucketConfig) -> Result<(), Duration> {
+        self.refill(cfg);
+
+        if self.tokens > 0 {
+            self.tokens -= 1;
+            Ok(())
+        } else {
+            let elapsed = Instant::now().duration_since(self.last_refill);
+            Err(cfg.refill.checked_sub(elapsed).unwrap_or_default())
+        }
+    }
+
+    /// Returns whether the bucket holds `burst` tokens again.
+    pub fn is_full(&mut self, cfg: BucketConfig) -> bool {
+        self.refill(cfg);
+        self.tokens >= cfg.burst
+    }
+
+    fn refill(&mut self, cfg: BucketConfig) {
+        let now = Instant::now();
+        if self.tokens >= cfg.burst {
+            self.tokens = cfg.burst;
+            self.last_refill = now;
+            return;
+        }
+
+        let refill = cfg.refill.as_nanos().max(1);
+        let gained = now.duration_since(self.last_refill).as_nanos() / refill;
+        if gained == 0 {
+            return;
+        }
+
+        let tokens = u128::from(self.tokens) + gained;
+        if tokens >= u128::from(cfg.burst) {
+            self.tokens = cfg.burst;
+            self.last_refill = now;
+        } else {
+            self.tokens = tokens as u32;
+            self.last_refill += *cfg.refill * gained as u32;
+        }
+    }
+}
+
+impl Default for TokenBucket {
+    fn default() -> TokenBucket {
+        TokenBucket::new()
+    }
+}
+
+/// Limits how often each user may perform an action, using a token bucket per user.
+pub struct ActionLimiter {
+    cfg: BucketConfig,
+    buckets: HashMap<Uuid, TokenBucket>,
+    last_cleanup: Instant,
+}
+
+impl ActionLimiter {
+    pub fn new(cfg: BucketConfig) -> ActionLimiter {
+        ActionLimiter {
+            cfg,
+            buckets: HashMap::new(),
+            last_cleanup: Instant::now(),
+        }
+    }
+
+    /// Checks if the action of `user` in this instant would be rate limited.
+    /// If not, then it registers the action.
+    pub fn check(&mut self, user: &Uuid) -> Result<(), Error> {
+        let cfg = self.cfg;
+        let now = Instant::now();
+        // full buckets behave like new ones, so they don't need to be kept
+        if now.duration_since(self.last_cleanup) >= *cfg.refill * cfg.burst.max(1) {
+            self.buckets.retain(|_, bucket| !bucket.is_full(cfg));
+            self.last_cleanup = now;
+        }
+
+        self.buckets
+            .entry(*user)
+            .or_default()
+            .take(cfg)
+            .map_err(|retry_after| ClientError::rate_limited(retry_after).into())
+    }
+}
@@ -0,0 +1,146 @@
+use crate::config::{LinkConfig, LinkPolicy};
+use crate::error::*;
+
+use regex::Regex;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Detects links, IP addresses and invites in messages.
+pub struct LinkFilter {
+    cfg: LinkConfig,
+    domain: Regex,
+    ipv4: Regex,
+    ipv6: Regex,
+    invite: Regex,
+}
+
+impl LinkFilter {
+    pub fn new(cfg: LinkConfig) -> LinkFilter {
+        LinkFilter {
+            cfg,
+            domain: Regex::new(
+                r"(?i)\b([a-z][a-z0-9+.-]*://|www\.)?((?:[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?\.)+([a-z]{2,63}))\b",
+            )
+            .unwrap(),
+            ipv4: Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b").unwrap(),
+            // whole words, so that `std::fs` is not mistaken for the address `d::f`
+            ipv6: Regex::new(r"(?i)[0-9a-z:]*:[0-9a-z:]*").unwrap(),
+            invite: Regex::new(
+                r"(?i)\b(?:discord(?:app)?\.com/invite|discord\.gg|dsc\.gg|discord\.io)\s*/\s*[a-z0-9-]+",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Checks whether `msg` contains a link, address or invite which is not allowed.
+    pub fn check(&self, msg: &str) -> Result<()> {
+        if self.cfg.block_invites && self.invite.is_match(msg) {
+            return Err(ClientError::LinkNotAllowed.into());
+        }
+
+        if self.cfg.block_addresses {
+            let ipv4 = self
+                .ipv4
+                .find_iter(msg)
+                .any(|found| found.as_str().parse::<Ipv4Addr>().is_ok());
+            let ipv6 = self
+                .ipv6
+                .find_iter(msg)
+                .any(|found| is_ipv6(found.as_str()));
+            if ipv4 || ipv6 {
+                return Err(ClientError::LinkNotAllowed.into());
+            }
+        }
+
+        for captures in self.domain.captures_iter(msg) {
+            // without a scheme or `www.`, things like `node.js` are only links
+            // if they end with a known top level domain
+            let tld = captures[3].to_lowercase();
+            if captures.get(1).is_none() && !self.cfg.tlds.contains(&tld) {
+                continue;
+            }
+
+            let domain = captures[2].to_lowercase();
+            if !self.is_allowed(&domain) {
+                return Err(ClientError::LinkNotAllowed.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_allowed(&self, domain: &str) -> bool {
+        let matches = |list: &[String]| {
+            list.iter().any(|entry| {
+                let entry = entry.trim_start_matches('.').to_lowercase();
+                domain == entry
+                    || domain.ends_with(&entry)
+                        && domain[..domain.len() - entry.len()].ends_with('.')
+            })
+        };
+
+        match self.cfg.policy {
+            LinkPolicy::AllowList => matches(&self.cfg.allowed),
+            LinkPolicy::DenyList => !matches(&self.cfg.denied),
+        }
+    }
+}
+
+/// Returns whether `word` is an IPv6 address which is worth blocking.
+///
+/// Addresses need at least two groups, so that `face::` or `::` are not treated as addresses.
+/// The unspecified and the loopback address are not blocked either.
+fn is_ipv6(word: &str) -> bool {
+    let groups = word.split(':').filter(|group| !group.is_empty()).count();
+    match word.parse::<Ipv6Addr>() {
+        Ok(addr) => groups >= 2 && !addr.is_unspecified() && !addr.is_loopback(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> LinkFilter {
+        LinkFilter::new(LinkConfig {
+            policy: LinkPolicy::AllowList,
+            ..LinkConfig::default()
+        })
+    }
+
+    #[test]
+    fn ordinary_messages() {
+        let filter = filter();
+        for msg in &[
+            "std::fs",
+            "Vec::new()",
+            "face::",
+            "see you :: later",
+            "::1",
+            "meet at 12:30:45",
+            "node.js",
+            "config.toml",
+            "lol.ok",
+            "see github.com",
+        ] {
+            assert!(filter.check(msg).is_ok(), "`{}` was blocked", msg);
+        }
+    }
+
+    #[test]
+    fn links_and_addresses() {
+        let filter = filter();
+        for msg in &[
+            "2001:db8::1",
+            "join [2001:db8::1]:25565",
+            "fe80::1ff:fe23:4567:890a",
+            "1.2.3.4:25565",
+            "https://example.org",
+            "www.example.org",
+            "example.com",
+            "discord.gg/abc",
+        ] {
+            assert!(filter.check(msg).is_err(), "`{}` was not blocked", msg);
+        }
+    }
+}
@@ -1,9 +1,26 @@
+mod audit;
 mod auth;
 mod chat;
 mod config;
+mod directory;
 mod error;
+mod filter;
+mod friends;
+mod groups;
+mod ip;
+mod keyset;
+mod limit;
+mod links;
 mod message;
+mod mock;
 mod moderation;
+mod normalize;
+mod preferences;
+mod provider;
+mod reports;
+mod revocation;
+mod spam;
+mod time;
 
 use config::Config;
 use error::*;
@@ -12,6 +29,7 @@ use structopt::*;
 
 use actix::*;
 use actix_web::{web, App, HttpServer};
+use std::net::SocketAddr;
 use uuid::Uuid;
 
 #[cfg(feature = "rust-tls")]
@@ -41,6 +59,18 @@ enum Opt {
         #[structopt(name = "uuid")]
         uuid: Option<Uuid>,
     },
+    /// Starts a local stand-in for the Mojang session server and the Minecraft services profile endpoint.
+    /// This should only be used for testing.
+    /// Copy the printed settings into the `mojang` segment to use it. The access token of every user is their name.
+    #[structopt(name = "mock-session-server")]
+    MockSessionServer {
+        /// The address the mock session server will listen at.
+        #[structopt(long = "address", default_value = "127.0.0.1:8081")]
+        address: SocketAddr,
+        /// The users which have joined, written as `name:uuid`.
+        #[structopt(name = "users")]
+        users: Vec<mock::MockUser>,
+    },
 }
 
 fn main() -> Result<()> {
@@ -60,13 +90,13 @@ fn main() -> Result<()> {
                     Err(ClientError::NotSupported.into())
                 }
             }?;
-            let token = auth.new_token(auth::UserInfo {
-                name,
-                uuid: uuid.unwrap_or_else(|| Uuid::from_u128(0)),
-            })?;
+            let uuid = uuid.unwrap_or_else(|| Uuid::from_u128(0));
+            let role = moderation::Moderation::new(config.moderation)?.role(&uuid);
+            let token = auth.new_token(auth::UserInfo { name, uuid }, role)?;
             println!("{}", token);
             Ok(())
         }
+        Opt::MockSessionServer { address, users } => mock::run(address, users),
     }
 }
 
@@ -74,10 +104,12 @@ fn start_server(config: Config) -> Result<()> {
     let system = System::new("axochat");
     let server_config = config.clone();
     let server = chat::ChatServer::new(server_config).start();
+    let net_config = config.net.clone();
 
     let server = HttpServer::new(move || {
         App::new()
             .data(server.clone())
+            .data(net_config.clone())
             .service(web::resource("/ws").to(chat::chat_route))
     });
 
@@ -1,49 +1,153 @@
 use crate::error::*;
 
-use crate::config::MsgConfig;
-use std::{collections::VecDeque, time::Instant};
+use crate::auth::UserInfo;
+use crate::chat::{GroupId, MessageId};
+use crate::config::{ChannelLimits, MsgConfig};
+use crate::limit::TokenBucket;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+/// The channel a message is sent in.
+#[derive(Clone, Copy, Debug)]
+pub enum Channel {
+    Public,
+    Private,
+}
+
+/// Restrictions moderators can put on the public chat, for example during raids.
+/// Moderators are not affected by them.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMode {
+    /// Users may only send one message every `slow_mode` seconds.
+    #[serde(default)]
+    pub slow_mode: Option<u64>,
+    /// Only users who were first seen before this time in seconds since the unix epoch
+    /// may send messages.
+    #[serde(default)]
+    pub seen_before: Option<u64>,
+    /// Only moderators may send messages.
+    #[serde(default)]
+    pub lockdown: bool,
+}
+
+impl ChatMode {
+    /// Returns the time of `seen_before`, or `None` if it is not set or can't be represented.
+    pub fn seen_before_time(&self) -> Option<SystemTime> {
+        UNIX_EPOCH.checked_add(Duration::from_secs(self.seen_before?))
+    }
+
+    /// Checks that the mode can be applied.
+    pub fn validate(&self) -> Result<()> {
+        if self.seen_before.is_some() && self.seen_before_time().is_none() {
+            return Err(ClientError::InvalidChatMode.into());
+        }
+        Ok(())
+    }
+}
 
 pub struct RateLimiter {
-    buf: VecDeque<(Instant, String)>,
-    cfg: MsgConfig,
+    public: TokenBucket,
+    private: TokenBucket,
+    last_public: Option<Instant>,
 }
 
 impl RateLimiter {
-    pub fn new(cfg: MsgConfig) -> RateLimiter {
+    pub fn new() -> RateLimiter {
         RateLimiter {
-            buf: VecDeque::with_capacity(cfg.max_messages),
-            cfg,
+            public: TokenBucket::new(),
+            private: TokenBucket::new(),
+            last_public: None,
         }
     }
 
-    /// Returns if a new message in this instant would be rate limited.
-    /// If not, then it registers the new message instant.
-    pub fn check_new_message(&mut self, message: String) -> bool {
+    /// Checks if a new public message in this instant would be sent less than `interval`
+    /// after the last one.
+    /// If not, then it registers the new message.
+    pub fn check_slow_mode(&mut self, interval: Duration) -> Result<()> {
         let now = Instant::now();
-        let limit = now - *self.cfg.count_duration;
+        if let Some(last) = self.last_public {
+            let elapsed = now.duration_since(last);
+            if elapsed < interval {
+                return Err(ClientError::rate_limited(interval - elapsed).into());
+            }
+        }
 
-        #[allow(clippy::op_ref)]
-        let last_index = self
-            .buf
-            .iter()
-            .take_while(|(time, _)| *time < limit)
-            .enumerate()
-            .map(|(i, _)| i)
-            .last()
-            .unwrap_or(0);
-        self.buf.drain(..last_index);
-
-        if self.buf.len() < self.cfg.max_messages {
-            let message_found = self.buf.iter().any(|(_, msg)| &message == msg);
-            if message_found {
-                true
-            } else {
-                self.buf.push_back((now, message));
-                false
+        self.last_public = Some(now);
+        Ok(())
+    }
+
+    /// Checks if a new message in this instant would be rate limited using `limits`.
+    /// If not, then it registers the new message.
+    pub fn check_new_message(&mut self, channel: Channel, limits: &ChannelLimits) -> Result<()> {
+        let res = match channel {
+            Channel::Public => self.public.take(limits.public),
+            Channel::Private => self.private.take(limits.private),
+        };
+        if res.is_ok() {
+            if let Channel::Public = channel {
+                self.last_public = Some(Instant::now());
             }
-        } else {
-            true
         }
+        res.map_err(|retry_after| ClientError::rate_limited(retry_after).into())
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> RateLimiter {
+        RateLimiter::new()
+    }
+}
+
+/// A message which was sent recently.
+#[derive(Clone, Serialize)]
+pub struct StoredMessage {
+    pub id: MessageId,
+    pub author: UserInfo,
+    /// The receiver, if this was a private message.
+    pub receiver: Option<Uuid>,
+    /// The group, if this was a group message.
+    pub group: Option<GroupId>,
+    pub content: String,
+}
+
+/// The most recent messages, so that they can be looked up by their id.
+pub struct MessageHistory {
+    buf: VecDeque<StoredMessage>,
+    size: usize,
+}
+
+impl MessageHistory {
+    pub fn new(size: usize) -> MessageHistory {
+        MessageHistory {
+            buf: VecDeque::with_capacity(size),
+            size,
+        }
+    }
+
+    pub fn push(&mut self, msg: StoredMessage) {
+        if self.size == 0 {
+            return;
+        }
+        if self.buf.len() >= self.size {
+            self.buf.pop_front();
+        }
+        self.buf.push_back(msg);
+    }
+
+    pub fn get(&self, id: MessageId) -> Option<&StoredMessage> {
+        self.buf.iter().rev().find(|msg| msg.id == id)
+    }
+
+    /// Returns the messages written by `author`, newest first.
+    pub fn by_author<'a>(&'a self, author: &'a Uuid) -> impl Iterator<Item = &'a StoredMessage> {
+        self.buf
+            .iter()
+            .rev()
+            .filter(move |msg| msg.author.uuid == *author)
     }
 }
 
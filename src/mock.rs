@@ -0,0 +1,239 @@
+use crate::error::*;
+use log::*;
+
+use actix::*;
+use actix_web::{http::header, web, App, HttpRequest, HttpResponse, HttpServer};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, fmt, net::SocketAddr, str::FromStr};
+use uuid::Uuid;
+
+/// A user which has joined according to the mock session server, written as `name:uuid`.
+#[derive(Debug, Clone)]
+pub struct MockUser {
+    name: String,
+    uuid: Uuid,
+}
+
+impl FromStr for MockUser {
+    type Err = InvalidMockUser;
+
+    fn from_str(s: &str) -> std::result::Result<MockUser, InvalidMockUser> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().filter(|name| !name.is_empty());
+        let uuid = parts.next().and_then(|uuid| uuid.parse().ok());
+        match (name, uuid) {
+            (Some(name), Some(uuid)) => Ok(MockUser {
+                name: name.to_owned(),
+                uuid,
+            }),
+            _ => Err(InvalidMockUser),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidMockUser;
+
+impl fmt::Display for InvalidMockUser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected a user in the format `name:uuid`")
+    }
+}
+
+#[derive(Deserialize)]
+struct HasJoinedQuery {
+    username: String,
+    #[serde(rename = "serverId")]
+    _server_id: String,
+}
+
+/// Runs a local stand-in for the Mojang session server and the Minecraft services profile endpoint,
+/// which can be used for testing the logins without network access.
+///
+/// Every user in `users` has joined every server; for other users `204 No Content` is returned,
+/// just like the real session server does.
+/// The access token of every user is their name.
+pub fn run(address: SocketAddr, users: Vec<MockUser>) -> Result<()> {
+    let system = System::new("axochat-mock");
+    let address = start(address, users)?;
+
+    println!(
+        "session_server = \"http://{}/session/minecraft/hasJoined\"",
+        address
+    );
+    println!(
+        "profile_endpoint = \"http://{}/minecraft/profile\"",
+        address
+    );
+    system.run()?;
+
+    Ok(())
+}
+
+/// Starts the mock servers in the current system and returns the address they listen at.
+/// Port `0` picks a free port.
+pub fn start(address: SocketAddr, users: Vec<MockUser>) -> Result<SocketAddr> {
+    let users: HashMap<String, MockUser> = users
+        .into_iter()
+        .map(|user| (user.name.to_lowercase(), user))
+        .collect();
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .data(users.clone())
+            .service(web::resource("/session/minecraft/hasJoined").route(web::get().to(has_joined)))
+            .service(web::resource("/minecraft/profile").route(web::get().to(profile)))
+    })
+    .workers(1)
+    .bind(address)?;
+    let address = server.addrs()[0];
+    server.start();
+
+    info!("Started mock session server at {}", address);
+    Ok(address)
+}
+
+fn has_joined(
+    users: web::Data<HashMap<String, MockUser>>,
+    query: web::Query<HasJoinedQuery>,
+) -> HttpResponse {
+    match users.get(&query.username.to_lowercase()) {
+        Some(user) => {
+            debug!("Mock session server accepted `{}`.", user.name);
+            HttpResponse::Ok().json(json!({
+                "id": user.uuid.to_simple().to_string(),
+                "name": user.name,
+                "properties": [],
+            }))
+        }
+        None => {
+            debug!("Mock session server rejected `{}`.", query.username);
+            HttpResponse::NoContent().finish()
+        }
+    }
+}
+
+fn profile(users: web::Data<HashMap<String, MockUser>>, request: HttpRequest) -> HttpResponse {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token.and_then(|token| users.get(&token.to_lowercase())) {
+        Some(user) => {
+            debug!("Mock profile endpoint accepted `{}`.", user.name);
+            HttpResponse::Ok().json(json!({
+                "id": user.uuid.to_simple().to_string(),
+                "name": user.name,
+                "skins": [],
+                "capes": [],
+            }))
+        }
+        None => {
+            debug!("Mock profile endpoint rejected the access token.");
+            HttpResponse::Unauthorized().finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{AuthInfo, SessionServer};
+    use crate::config::MojangConfig;
+
+    const NOTCH: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+    /// Starts the mock servers with the user `Notch` and a session server using them.
+    fn session_server() -> (SystemRunner, SessionServer) {
+        let system = System::new("axochat-mock-test");
+        let user = format!("Notch:{}", NOTCH).parse().unwrap();
+        let address = start("127.0.0.1:0".parse().unwrap(), vec![user]).unwrap();
+
+        let cfg = MojangConfig {
+            session_server: format!("http://{}/session/minecraft/hasJoined", address),
+            profile_endpoint: format!("http://{}/minecraft/profile", address),
+            retries: 0,
+            ..MojangConfig::default()
+        };
+        (system, SessionServer::new(&cfg).unwrap())
+    }
+
+    fn assert_login_failed(result: Result<AuthInfo>) {
+        match result {
+            Err(Error::AxoChat {
+                source: ClientError::LoginFailed,
+            }) => {}
+            other => panic!(
+                "expected a failed login, got {:?}",
+                other.map(|info| info.name)
+            ),
+        }
+    }
+
+    #[test]
+    fn has_joined() {
+        let (mut system, session_server) = session_server();
+        let info = system
+            .block_on(session_server.has_joined("notch", "server-id"))
+            .unwrap();
+        assert_eq!(info.id, NOTCH.parse::<Uuid>().unwrap());
+        assert_eq!(info.name, "Notch");
+    }
+
+    #[test]
+    fn has_not_joined() {
+        let (mut system, session_server) = session_server();
+        let result = system.block_on(session_server.has_joined("jeb_", "server-id"));
+        assert_login_failed(result);
+    }
+
+    #[test]
+    fn profile() {
+        let (mut system, session_server) = session_server();
+        let info = system.block_on(session_server.profile("Notch")).unwrap();
+        assert_eq!(info.id, NOTCH.parse::<Uuid>().unwrap());
+        assert_eq!(info.name, "Notch");
+    }
+
+    #[test]
+    fn profile_unauthorized() {
+        let (mut system, session_server) = session_server();
+        let result = system.block_on(session_server.profile("jeb_"));
+        assert_login_failed(result);
+    }
+
+    #[test]
+    fn profile_malformed() {
+        let mut system = System::new("axochat-mock-test");
+        let server = HttpServer::new(|| {
+            App::new().route(
+                "/minecraft/profile",
+                web::get().to(|| HttpResponse::Ok().body(r#"{"id": "#)),
+            )
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let address = server.addrs()[0];
+        server.start();
+
+        let cfg = MojangConfig {
+            profile_endpoint: format!("http://{}/minecraft/profile", address),
+            retries: 0,
+            ..MojangConfig::default()
+        };
+        let session_server = SessionServer::new(&cfg).unwrap();
+        let result = system.block_on(session_server.profile("Notch"));
+        assert_login_failed(result);
+    }
+
+    #[test]
+    fn parse_user() {
+        let user: MockUser = format!("Notch:{}", NOTCH).parse().unwrap();
+        assert_eq!(user.name, "Notch");
+        assert!("Notch".parse::<MockUser>().is_err());
+        assert!(format!(":{}", NOTCH).parse::<MockUser>().is_err());
+    }
+}
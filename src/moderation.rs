@@ -1,32 +1,101 @@
 use crate::config::ModConfig;
 use crate::error::*;
-use std::collections::HashSet;
+use crate::ip::IpRange;
+use log::*;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
 use std::{
-    fs::{File, OpenOptions},
-    io::{BufRead, BufReader, BufWriter, Write},
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::IpAddr,
     path::Path,
 };
 use uuid::Uuid;
 
+/// The role of a user, which is embedded into issued JWTs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum Role {
+    #[default]
+    User,
+    Moderator,
+    Admin,
+}
+
+/// Something other than a user which can be banned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BanTarget {
+    /// A single IP address or a range of addresses.
+    Ip(IpRange),
+    /// An identifier sent by the client when logging in.
+    Identifier(String),
+}
+
+impl BanTarget {
+    /// Returns whether a connection from `ip` using the client `identifier` is affected.
+    pub fn matches(&self, ip: Option<IpAddr>, identifier: Option<&str>) -> bool {
+        match self {
+            BanTarget::Ip(range) => ip.is_some_and(|ip| range.contains(&ip)),
+            BanTarget::Identifier(banned) => identifier == Some(banned.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetBan {
+    pub target: BanTarget,
+    /// The users which were seen using the banned target, for review by moderators.
+    #[serde(default)]
+    pub linked: BTreeSet<Uuid>,
+}
+
 pub struct Moderation {
     config: ModConfig,
+    admins: HashSet<Uuid>,
     moderators: HashSet<Uuid>,
     banned: HashSet<Uuid>,
+    shadow_banned: HashSet<Uuid>,
+    target_bans: Vec<TargetBan>,
 }
 
 impl Moderation {
     pub fn new(config: ModConfig) -> Result<Moderation> {
+        let admins = read_ids(&config.admins)?;
         let moderators = read_ids(&config.moderators)?;
         let banned = read_ids(&config.banned)?;
+        let shadow_banned = read_ids(&config.shadow_banned)?;
+        let target_bans = match fs::read(&config.target_bans) {
+            Ok(input) => serde_json::from_slice(&input)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
         Ok(Moderation {
             config,
+            admins,
             moderators,
             banned,
+            shadow_banned,
+            target_bans,
         })
     }
 
+    /// Admins are moderators as well.
     pub fn is_moderator(&self, user: &Uuid) -> bool {
-        self.moderators.contains(user)
+        self.moderators.contains(user) || self.is_admin(user)
+    }
+
+    pub fn is_admin(&self, user: &Uuid) -> bool {
+        self.admins.contains(user)
+    }
+
+    pub fn role(&self, user: &Uuid) -> Role {
+        if self.is_admin(user) {
+            Role::Admin
+        } else if self.is_moderator(user) {
+            Role::Moderator
+        } else {
+            Role::User
+        }
     }
 
     /// Ban user if user is not a moderator.
@@ -35,26 +104,38 @@ impl Moderation {
             Err(ClientError::NotPermitted.into())
         } else {
             if self.banned.insert(user.clone()) {
-                let mut file = OpenOptions::new()
-                    .append(true)
-                    .create(true)
-                    .open(&self.config.banned)?;
+                append_id(&self.config.banned, user)?;
+            }
+
+            Ok(())
+        }
+    }
 
-                writeln!(file, "{}", user.to_hyphenated())?;
+    /// Shadow ban user if user is not a moderator.
+    pub fn shadow_ban(&mut self, user: &Uuid) -> Result<()> {
+        if self.is_moderator(user) {
+            Err(ClientError::NotPermitted.into())
+        } else {
+            if self.shadow_banned.insert(*user) {
+                append_id(&self.config.shadow_banned, user)?;
             }
 
             Ok(())
         }
     }
 
+    /// Lifts both normal bans and shadow bans.
     pub fn unban(&mut self, user: &Uuid) -> Result<()> {
-        if self.banned.remove(user) {
-            let mut writer = BufWriter::new(File::create(&self.config.banned)?);
-
-            for banned in &self.banned {
-                writeln!(writer, "{}", banned)?;
-            }
+        let banned = self.banned.remove(user);
+        if banned {
+            write_ids(&self.config.banned, &self.banned)?;
+        }
+        let shadow_banned = self.shadow_banned.remove(user);
+        if shadow_banned {
+            write_ids(&self.config.shadow_banned, &self.shadow_banned)?;
+        }
 
+        if banned || shadow_banned {
             Ok(())
         } else {
             Err(ClientError::NotBanned.into())
@@ -64,6 +145,96 @@ impl Moderation {
     pub fn is_banned(&self, user: &Uuid) -> bool {
         self.banned.contains(user)
     }
+
+    pub fn is_shadow_banned(&self, user: &Uuid) -> bool {
+        self.shadow_banned.contains(user)
+    }
+
+    /// Bans `target` and links `users` to the ban.
+    /// If `target` is already banned, `users` are added to the existing ban.
+    pub fn ban_target(&mut self, target: BanTarget, users: Vec<Uuid>) -> Result<()> {
+        match self.target_bans.iter_mut().find(|ban| ban.target == target) {
+            Some(ban) => ban.linked.extend(users),
+            None => self.target_bans.push(TargetBan {
+                target,
+                linked: users.into_iter().collect(),
+            }),
+        }
+        self.save_target_bans()
+    }
+
+    pub fn unban_target(&mut self, target: &BanTarget) -> Result<()> {
+        let len = self.target_bans.len();
+        self.target_bans.retain(|ban| ban.target != *target);
+        if self.target_bans.len() == len {
+            Err(ClientError::NotBanned.into())
+        } else {
+            self.save_target_bans()
+        }
+    }
+
+    pub fn target_bans(&self) -> &[TargetBan] {
+        &self.target_bans
+    }
+
+    /// Returns whether a connection from `ip` using the client `identifier` is banned.
+    pub fn is_target_banned(&self, ip: Option<IpAddr>, identifier: Option<&str>) -> bool {
+        self.target_bans
+            .iter()
+            .any(|ban| ban.target.matches(ip, identifier))
+    }
+
+    /// Returns whether `user` logging in from `ip` using the client `identifier` is banned.
+    /// If they are, the user is linked to the matching bans.
+    /// Moderators are never banned this way.
+    pub fn check_target_ban(
+        &mut self,
+        ip: Option<IpAddr>,
+        identifier: Option<&str>,
+        user: &Uuid,
+    ) -> bool {
+        if self.is_moderator(user) {
+            return false;
+        }
+
+        let mut banned = false;
+        let mut changed = false;
+        for ban in &mut self.target_bans {
+            if ban.target.matches(ip, identifier) {
+                banned = true;
+                changed |= ban.linked.insert(*user);
+            }
+        }
+        if changed {
+            if let Err(err) = self.save_target_bans() {
+                warn!("Could not save bans: {}", err);
+            }
+        }
+
+        banned
+    }
+
+    fn save_target_bans(&self) -> Result<()> {
+        fs::write(
+            &self.config.target_bans,
+            serde_json::to_vec_pretty(&self.target_bans)?,
+        )?;
+        Ok(())
+    }
+}
+
+fn append_id(path: &Path, id: &Uuid) -> Result<()> {
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    writeln!(file, "{}", id.to_hyphenated())?;
+    Ok(())
+}
+
+fn write_ids(path: &Path, ids: &HashSet<Uuid>) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for id in ids {
+        writeln!(writer, "{}", id)?;
+    }
+    Ok(())
 }
 
 fn read_ids(path: &Path) -> Result<HashSet<Uuid>> {
@@ -0,0 +1,206 @@
+use std::ops::Range;
+
+/// A message normalized for comparison and filtering.
+///
+/// Normalizing lowercases the text, maps homoglyphs and common character substitutions to
+/// their ASCII counterparts and removes punctuation.
+pub struct Normalized {
+    text: String,
+    /// The byte range in the original text every char of `text` originates from.
+    spans: Vec<Range<usize>>,
+}
+
+impl Normalized {
+    /// Normalizes `original` for comparing it with other messages.
+    ///
+    /// Whitespace is removed and repeated characters are collapsed,
+    /// so that `H e l l l o!` and `hеllo` (with a cyrillic `е`) both become `helo`.
+    pub fn new(original: &str) -> Normalized {
+        Normalized::normalize(original, false)
+    }
+
+    /// Normalizes `original` for matching words in it.
+    ///
+    /// Words are separated by a single space and repeated characters are kept,
+    /// so that `H.e.l.l.o   W0rld!` becomes `hello world`.
+    pub fn words(original: &str) -> Normalized {
+        Normalized::normalize(original, true)
+    }
+
+    fn normalize(original: &str, keep_words: bool) -> Normalized {
+        let mut text = String::with_capacity(original.len());
+        let mut spans: Vec<Range<usize>> = Vec::with_capacity(original.len());
+        let mut last = None;
+
+        for (index, ch) in original.char_indices() {
+            let end = index + ch.len_utf8();
+            for ch in ch.to_lowercase().map(fold) {
+                let ch = if ch.is_alphanumeric() {
+                    ch
+                } else if keep_words && ch.is_whitespace() && last.is_some() {
+                    ' '
+                } else {
+                    continue;
+                };
+
+                if last == Some(ch) && (ch == ' ' || !keep_words) {
+                    if let Some(span) = spans.last_mut() {
+                        span.end = end;
+                    }
+                } else {
+                    text.push(ch);
+                    spans.push(index..end);
+                    last = Some(ch);
+                }
+            }
+        }
+
+        if text.ends_with(' ') {
+            text.pop();
+            spans.pop();
+        }
+
+        Normalized { text, spans }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Maps a byte range of the normalized text to the byte range of the original text.
+    pub fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        let first = self.text[..range.start].chars().count();
+        let count = self.text[range].chars().count();
+        if count == 0 {
+            return 0..0;
+        }
+
+        self.spans[first].start..self.spans[first + count - 1].end
+    }
+}
+
+/// Maps a lowercase character to the ASCII character it is commonly used in place of.
+fn fold(ch: char) -> char {
+    match ch {
+        // fullwidth forms
+        '\u{ff01}'..='\u{ff5e}' => {
+            std::char::from_u32(ch as u32 - 0xfee0).map_or(ch, |ch| fold(ch.to_ascii_lowercase()))
+        }
+        // cyrillic
+        'а' => 'a',
+        'в' => 'b',
+        'е' | 'ё' => 'e',
+        'і' | 'ї' => 'i',
+        'ј' => 'j',
+        'к' => 'k',
+        'м' => 'm',
+        'н' => 'h',
+        'о' => 'o',
+        'р' => 'p',
+        'с' => 'c',
+        'т' => 't',
+        'у' => 'y',
+        'х' => 'x',
+        'ѕ' => 's',
+        'ԁ' => 'd',
+        'ԛ' => 'q',
+        'ԝ' => 'w',
+        // greek
+        'α' => 'a',
+        'β' => 'b',
+        'ε' => 'e',
+        'η' => 'n',
+        'ι' => 'i',
+        'κ' => 'k',
+        'ν' => 'v',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'τ' => 't',
+        'υ' => 'u',
+        'χ' => 'x',
+        // latin lookalikes
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ɡ' => 'g',
+        'ı' => 'i',
+        'ß' => 's',
+        // common substitutions
+        '0' => 'o',
+        '1' | '|' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        _ => ch,
+    }
+}
+
+/// Returns how similar `a` and `b` are, from `0.0` (completely different) to `1.0` (equal).
+///
+/// The similarity is based on the levenshtein distance of both strings.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev + if ca == cb { 0 } else { 1 };
+            prev = row[j + 1];
+            row[j + 1] = substitution.min(prev + 1).min(row[j] + 1);
+        }
+    }
+
+    1.0 - row[b.len()] as f64 / max_len as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(Normalized::new("H e l l l o!").as_str(), "helo");
+        assert_eq!(Normalized::new("h\u{435}llo").as_str(), "helo");
+        assert_eq!(Normalized::new("\u{ff28}ello").as_str(), "helo");
+        assert_eq!(Normalized::new("?!").as_str(), "");
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            Normalized::words("H.e.l.l.o   W0rld!").as_str(),
+            "hello world"
+        );
+        assert_eq!(Normalized::words("  fr33  ").as_str(), "free");
+    }
+
+    #[test]
+    fn original_range() {
+        let original = "H.e.l.l.o   W0rld!";
+        let normalized = Normalized::words(original);
+        let start = normalized.as_str().find("world").unwrap();
+        let range = normalized.original_range(start..start + "world".len());
+        assert_eq!(&original[range], "W0rld");
+    }
+
+    #[test]
+    fn similar() {
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("helo", "helo"), 1.0);
+        assert_eq!(similarity("abcd", "wxyz"), 0.0);
+        assert_eq!(similarity("heloi", "helo2"), 0.8);
+    }
+}
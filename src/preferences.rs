@@ -0,0 +1,69 @@
+use crate::error::*;
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, io, path::PathBuf};
+use uuid::Uuid;
+
+/// Who may send private messages to a user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Privacy {
+    #[default]
+    Everyone,
+    FriendsOnly,
+    ModeratorsOnly,
+    Nobody,
+}
+
+/// The settings a user can change at runtime.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Preferences {
+    #[serde(default)]
+    pub privacy: Privacy,
+}
+
+/// The preferences users set explicitly, stored as JSON.
+///
+/// Changes are only written by [`PreferenceStore::save_if_changed`].
+pub struct PreferenceStore {
+    path: PathBuf,
+    preferences: HashMap<Uuid, Preferences>,
+    changed: bool,
+}
+
+impl PreferenceStore {
+    pub fn new(path: PathBuf) -> Result<PreferenceStore> {
+        let preferences = match fs::read(&path) {
+            Ok(input) => serde_json::from_slice(&input)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(PreferenceStore {
+            path,
+            preferences,
+            changed: false,
+        })
+    }
+
+    /// Returns the preferences of `user`, or the default ones if they never set any.
+    pub fn get(&self, user: &Uuid) -> Preferences {
+        self.preferences.get(user).cloned().unwrap_or_default()
+    }
+
+    pub fn contains(&self, user: &Uuid) -> bool {
+        self.preferences.contains_key(user)
+    }
+
+    pub fn set(&mut self, user: Uuid, preferences: Preferences) {
+        self.preferences.insert(user, preferences);
+        self.changed = true;
+    }
+
+    /// Writes the preferences if they were changed since they were last written.
+    pub fn save_if_changed(&mut self) -> Result<()> {
+        if self.changed {
+            fs::write(&self.path, serde_json::to_vec_pretty(&self.preferences)?)?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+}
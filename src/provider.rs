@@ -0,0 +1,187 @@
+use crate::auth::{Authenticator, SessionServer, UserInfo};
+use crate::config::Config;
+use crate::error::*;
+
+use futures::{future, Future};
+use serde::Serialize;
+use std::{collections::HashMap, rc::Rc};
+use uuid::Uuid;
+
+/// How a connection was authenticated.
+#[derive(Debug, Serialize, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AuthMethod {
+    Mojang,
+    JWT,
+    MinecraftServices,
+    Anonymous,
+}
+
+/// What a client sent to prove its identity.
+pub enum Credentials {
+    Mojang {
+        name: String,
+        uuid: Uuid,
+        /// The session hash sent to the client in `MojangInfo`, if it was requested.
+        session_hash: Option<String>,
+    },
+    JWT {
+        token: String,
+    },
+    MinecraftServices {
+        token: String,
+    },
+    Anonymous,
+}
+
+impl Credentials {
+    pub fn method(&self) -> AuthMethod {
+        match self {
+            Credentials::Mojang { .. } => AuthMethod::Mojang,
+            Credentials::JWT { .. } => AuthMethod::JWT,
+            Credentials::MinecraftServices { .. } => AuthMethod::MinecraftServices,
+            Credentials::Anonymous => AuthMethod::Anonymous,
+        }
+    }
+}
+
+/// Verifies credentials of one authentication method.
+pub trait AuthProvider {
+    /// Verifies `credentials` and returns the identity of the user.
+    ///
+    /// The credentials always belong to the method the provider is registered for.
+    /// A `ClientError` is sent to the client as is; any other error results in `LoginFailed`.
+    fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Box<dyn Future<Item = UserInfo, Error = Error>>;
+}
+
+/// Creates the providers of all authentication methods enabled in `config`.
+pub fn providers(
+    config: &Config,
+    authenticator: Option<Rc<Authenticator>>,
+    session_server: Rc<SessionServer>,
+) -> HashMap<AuthMethod, Box<dyn AuthProvider>> {
+    let mut providers: HashMap<AuthMethod, Box<dyn AuthProvider>> = HashMap::new();
+    if config.mojang.allow_mojang {
+        providers.insert(
+            AuthMethod::Mojang,
+            Box::new(MojangProvider(session_server.clone())),
+        );
+    }
+    if config.mojang.allow_minecraft_services {
+        providers.insert(
+            AuthMethod::MinecraftServices,
+            Box::new(MinecraftServicesProvider(session_server)),
+        );
+    }
+    if let Some(authenticator) = authenticator {
+        providers.insert(AuthMethod::JWT, Box::new(JWTProvider(authenticator)));
+    }
+    if config
+        .auth
+        .as_ref()
+        .is_some_and(|auth| auth.allow_anonymous)
+    {
+        providers.insert(AuthMethod::Anonymous, Box::new(AnonymousProvider));
+    }
+
+    providers
+}
+
+/// Checks that the client joined a server with the session hash using the Mojang session server.
+struct MojangProvider(Rc<SessionServer>);
+
+impl AuthProvider for MojangProvider {
+    fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Box<dyn Future<Item = UserInfo, Error = Error>> {
+        let (name, uuid, session_hash) = match credentials {
+            Credentials::Mojang {
+                name,
+                uuid,
+                session_hash: Some(session_hash),
+            } => (name, uuid, session_hash),
+            Credentials::Mojang { .. } => {
+                return Box::new(future::err(ClientError::MojangRequestMissing.into()))
+            }
+            _ => return Box::new(future::err(ClientError::NotSupported.into())),
+        };
+
+        Box::new(
+            self.0
+                .has_joined(&name, &session_hash)
+                .and_then(move |profile| {
+                    if profile.id == uuid {
+                        // the name sent by the client is not trustworthy
+                        Ok(UserInfo {
+                            name: profile.name,
+                            uuid: profile.id,
+                        })
+                    } else {
+                        // the client joined with another account
+                        Err(ClientError::LoginFailed.into())
+                    }
+                }),
+        )
+    }
+}
+
+/// Looks up the profile of a Minecraft services access token.
+struct MinecraftServicesProvider(Rc<SessionServer>);
+
+impl AuthProvider for MinecraftServicesProvider {
+    fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Box<dyn Future<Item = UserInfo, Error = Error>> {
+        let token = match credentials {
+            Credentials::MinecraftServices { token } => token,
+            _ => return Box::new(future::err(ClientError::NotSupported.into())),
+        };
+
+        Box::new(self.0.profile(&token).map(|profile| UserInfo {
+            name: profile.name,
+            uuid: profile.id,
+        }))
+    }
+}
+
+/// Validates JWTs issued by this server.
+struct JWTProvider(Rc<Authenticator>);
+
+impl AuthProvider for JWTProvider {
+    fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Box<dyn Future<Item = UserInfo, Error = Error>> {
+        let result = match credentials {
+            Credentials::JWT { token } => self.0.auth(&token),
+            _ => Err(ClientError::NotSupported.into()),
+        };
+        Box::new(future::result(result))
+    }
+}
+
+/// Gives every connection a new random identity.
+struct AnonymousProvider;
+
+impl AuthProvider for AnonymousProvider {
+    fn authenticate(
+        &self,
+        credentials: Credentials,
+    ) -> Box<dyn Future<Item = UserInfo, Error = Error>> {
+        let result = match credentials {
+            Credentials::Anonymous => {
+                let uuid = Uuid::new_v4();
+                Ok(UserInfo {
+                    name: format!("Anonymous-{}", &uuid.to_simple().to_string()[..8]),
+                    uuid,
+                })
+            }
+            _ => Err(ClientError::NotSupported.into()),
+        };
+        Box::new(future::result(result))
+    }
+}
@@ -0,0 +1,125 @@
+use crate::auth::UserInfo;
+use crate::chat::MessageId;
+use crate::error::*;
+use crate::time::unix_secs;
+
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf, time::SystemTime};
+use uuid::Uuid;
+
+/// A report of a user or one of their messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub id: u64,
+    pub reporter: UserInfo,
+    /// The reported user.
+    pub user: Uuid,
+    /// The reported message as it was at the time of the report.
+    pub message: Option<ReportedMessage>,
+    pub reason: String,
+    /// The time of the report in seconds since the unix epoch.
+    pub time: u64,
+    /// The moderator who resolved the report.
+    #[serde(default)]
+    pub resolved_by: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportedMessage {
+    pub id: MessageId,
+    pub author_info: UserInfo,
+    pub content: String,
+    pub private: bool,
+}
+
+/// The persisted reports, stored as JSON.
+///
+/// Changes are only written by [`Reports::save_if_changed`].
+pub struct Reports {
+    path: PathBuf,
+    reports: Vec<Report>,
+    changed: bool,
+}
+
+impl Reports {
+    pub fn new(path: PathBuf) -> Result<Reports> {
+        let reports = match fs::read(&path) {
+            Ok(input) => serde_json::from_slice(&input)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Reports {
+            path,
+            reports,
+            changed: false,
+        })
+    }
+
+    /// Adds a new report unless the reporter already has an open report of the same user.
+    pub fn add(
+        &mut self,
+        reporter: UserInfo,
+        user: Uuid,
+        message: Option<ReportedMessage>,
+        reason: String,
+    ) -> Result<&Report> {
+        let duplicate = self
+            .open()
+            .any(|report| report.reporter.uuid == reporter.uuid && report.user == user);
+        if duplicate {
+            return Err(ClientError::AlreadyReported.into());
+        }
+
+        let id = self.reports.last().map_or(1, |report| report.id + 1);
+        self.reports.push(Report {
+            id,
+            reporter,
+            user,
+            message,
+            reason,
+            time: unix_secs(SystemTime::now()),
+            resolved_by: None,
+        });
+        self.changed = true;
+
+        Ok(self.reports.last().unwrap())
+    }
+
+    /// Returns the reports which were not resolved yet.
+    pub fn open(&self) -> impl Iterator<Item = &Report> {
+        self.reports
+            .iter()
+            .filter(|report| report.resolved_by.is_none())
+    }
+
+    /// Returns all reports of `user`, including resolved ones.
+    pub fn of_user<'a>(&'a self, user: &'a Uuid) -> impl Iterator<Item = &'a Report> {
+        self.reports
+            .iter()
+            .filter(move |report| report.user == *user)
+    }
+
+    pub fn resolve(&mut self, id: u64, moderator: Uuid) -> Result<()> {
+        match self
+            .reports
+            .iter_mut()
+            .find(|report| report.id == id && report.resolved_by.is_none())
+        {
+            Some(report) => {
+                report.resolved_by = Some(moderator);
+                self.changed = true;
+                Ok(())
+            }
+            None => Err(ClientError::InvalidId.into()),
+        }
+    }
+
+    /// Writes the reports if they were changed since they were last written.
+    pub fn save_if_changed(&mut self) -> Result<()> {
+        if self.changed {
+            fs::write(&self.path, serde_json::to_vec_pretty(&self.reports)?)?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+}
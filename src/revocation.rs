@@ -0,0 +1,77 @@
+use crate::error::*;
+use crate::time::{unix_millis, unix_secs};
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, io, path::PathBuf, time::SystemTime};
+use uuid::Uuid;
+
+#[derive(Default, Serialize, Deserialize)]
+struct RevocationData {
+    /// Tokens of a user issued at or before this time in seconds since the unix epoch
+    /// are revoked. Only read from older files, see `revoked_users`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    users: HashMap<Uuid, u64>,
+    /// Tokens of a user issued before this time in milliseconds since the unix epoch
+    /// are revoked.
+    #[serde(default)]
+    revoked_users: HashMap<Uuid, u64>,
+    /// Single revoked tokens by their id, with their expiration time.
+    #[serde(default)]
+    tokens: HashMap<String, u64>,
+}
+
+/// The persisted list of revoked tokens, stored as JSON.
+pub struct Revocations {
+    path: PathBuf,
+    data: RevocationData,
+}
+
+impl Revocations {
+    pub fn new(path: PathBuf) -> Result<Revocations> {
+        let mut data: RevocationData = match fs::read(&path) {
+            Ok(input) => serde_json::from_slice(&input)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => RevocationData::default(),
+            Err(err) => return Err(err.into()),
+        };
+        for (user, secs) in data.users.drain() {
+            let millis = (secs + 1) * 1000;
+            let revoked = data.revoked_users.entry(user).or_insert(millis);
+            *revoked = millis.max(*revoked);
+        }
+        Ok(Revocations { path, data })
+    }
+
+    /// Returns whether the token with the id `jti` of `user` issued at `issued`
+    /// in milliseconds since the unix epoch is revoked.
+    pub fn is_revoked(&self, user: &Uuid, jti: &str, issued: u64) -> bool {
+        self.data.tokens.contains_key(jti)
+            || self
+                .data
+                .revoked_users
+                .get(user)
+                .is_some_and(|before| issued < *before)
+    }
+
+    /// Revokes every token of `user` issued until now.
+    pub fn revoke_user(&mut self, user: Uuid) -> Result<()> {
+        self.data
+            .revoked_users
+            .insert(user, unix_millis(SystemTime::now()));
+        self.save()
+    }
+
+    /// Revokes the token with the id `jti`, which expires at `exp`.
+    pub fn revoke_token(&mut self, jti: String, exp: u64) -> Result<()> {
+        self.data.tokens.insert(jti, exp);
+        self.save()
+    }
+
+    fn save(&mut self) -> Result<()> {
+        // expired tokens are invalid anyway
+        let now = unix_secs(SystemTime::now());
+        self.data.tokens.retain(|_, exp| *exp > now);
+
+        fs::write(&self.path, serde_json::to_vec_pretty(&self.data)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use crate::config::SpamConfig;
+use crate::error::*;
+use crate::normalize::{similarity, Normalized};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Detects repeated messages of a single user and floods of the same content by many users.
+///
+/// Messages are compared after normalizing them,
+/// so small variations of the same message are detected as well.
+pub struct SpamDetector {
+    cfg: SpamConfig,
+    duration: Duration,
+    /// The recent messages of every user.
+    users: HashMap<Uuid, VecDeque<(Instant, String)>>,
+    /// The users who recently sent a message, by message.
+    floods: HashMap<String, Vec<(Instant, Uuid)>>,
+    last_cleanup: Instant,
+}
+
+impl SpamDetector {
+    /// Creates a detector which remembers messages for `duration`.
+    pub fn new(cfg: SpamConfig, duration: Duration) -> SpamDetector {
+        SpamDetector {
+            cfg,
+            duration,
+            users: HashMap::new(),
+            floods: HashMap::new(),
+            last_cleanup: Instant::now(),
+        }
+    }
+
+    /// Checks if `message` by `user` is spam.
+    /// If not, then it registers the new message.
+    /// Messages without letters or digits, like `?` or emojis, are never spam.
+    pub fn check_new_message(&mut self, user: &Uuid, message: &str) -> Result<()> {
+        let now = Instant::now();
+        self.cleanup(now);
+
+        let normalized = Normalized::new(message);
+        let normalized = normalized.as_str();
+        if normalized.is_empty() {
+            return Ok(());
+        }
+        let long = normalized.chars().count() >= self.cfg.min_length;
+        let threshold = self.cfg.similarity;
+
+        let history = self.users.entry(*user).or_default();
+        while let Some((time, _)) = history.front() {
+            if now.duration_since(*time) < self.duration {
+                break;
+            }
+            history.pop_front();
+        }
+        let repeated = history
+            .iter()
+            .any(|(_, msg)| msg == normalized || long && similarity(msg, normalized) >= threshold);
+        if repeated {
+            return Err(ClientError::Spam.into());
+        }
+
+        if long {
+            let duration = self.duration;
+            let senders = self.floods.entry(normalized.to_owned()).or_default();
+            senders.retain(|(time, sender)| now.duration_since(*time) < duration && sender != user);
+            if senders.len() + 1 >= self.cfg.flood_users {
+                return Err(ClientError::Spam.into());
+            }
+            senders.push((now, *user));
+        }
+
+        if history.len() >= self.cfg.history {
+            history.pop_front();
+        }
+        history.push_back((now, normalized.to_owned()));
+
+        Ok(())
+    }
+
+    /// Removes everything which is older than `duration`.
+    fn cleanup(&mut self, now: Instant) {
+        if now.duration_since(self.last_cleanup) < self.duration {
+            return;
+        }
+        self.last_cleanup = now;
+
+        let duration = self.duration;
+        let recent = |time: &Instant| now.duration_since(*time) < duration;
+        self.users
+            .retain(|_, history| history.back().is_some_and(|(time, _)| recent(time)));
+        self.floods
+            .retain(|_, senders| senders.iter().any(|(time, _)| recent(time)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> SpamDetector {
+        SpamDetector::new(SpamConfig::default(), Duration::from_secs(60))
+    }
+
+    #[test]
+    fn repeated_message() {
+        let mut spam = detector();
+        let user = Uuid::from_u128(1);
+        assert!(spam.check_new_message(&user, "hello1").is_ok());
+        assert!(spam.check_new_message(&user, "hello2").is_err());
+        assert!(spam.check_new_message(&user, "something else").is_ok());
+    }
+
+    #[test]
+    fn short_messages() {
+        let mut spam = detector();
+        let user = Uuid::from_u128(1);
+        assert!(spam.check_new_message(&user, "gg").is_ok());
+        assert!(spam.check_new_message(&user, "ok").is_ok());
+        assert!(spam.check_new_message(&user, "GG!").is_err());
+    }
+
+    #[test]
+    fn punctuation_only() {
+        let mut spam = detector();
+        let user = Uuid::from_u128(1);
+        assert!(spam.check_new_message(&user, "?").is_ok());
+        assert!(spam.check_new_message(&user, "!").is_ok());
+        assert!(spam.check_new_message(&user, "\u{1f600}").is_ok());
+        assert!(spam.check_new_message(&user, "\u{1f600}").is_ok());
+    }
+
+    #[test]
+    fn flood() {
+        let mut spam = detector();
+        assert!(spam
+            .check_new_message(&Uuid::from_u128(1), "join my server")
+            .is_ok());
+        assert!(spam
+            .check_new_message(&Uuid::from_u128(2), "join my server")
+            .is_ok());
+        assert!(spam
+            .check_new_message(&Uuid::from_u128(3), "join my server")
+            .is_err());
+    }
+}
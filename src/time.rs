@@ -0,0 +1,15 @@
+use std::time::SystemTime;
+
+/// Returns the seconds since the unix epoch.
+pub fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .expect("system time is somehow before the unix epoch")
+        .as_secs()
+}
+
+/// Returns the milliseconds since the unix epoch.
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .expect("system time is somehow before the unix epoch")
+        .as_millis() as u64
+}